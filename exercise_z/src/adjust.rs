@@ -0,0 +1,801 @@
+use crate::transform::{is_16_bit, with_color_type, Channel};
+use image::{DynamicImage, GenericImageView, ImageBuffer, Rgba};
+use std::str::FromStr;
+
+/// The number of bins luminance is counted in for equalization.
+const EQUALIZE_BINS: usize = 256;
+
+/// The color channels a tonal adjustment applies to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Channels {
+  All,
+  Red,
+  Green,
+  Blue,
+}
+
+impl Channels {
+  /// The names `Channels::from_str` accepts.
+  pub const NAMES: [&'static str; 4] = ["all", "red", "green", "blue"];
+
+  /// Whether red, green and blue are included.
+  fn mask(self) -> [bool; 3] {
+    match self {
+      Channels::All => [true, true, true],
+      Channels::Red => [true, false, false],
+      Channels::Green => [false, true, false],
+      Channels::Blue => [false, false, true],
+    }
+  }
+}
+
+impl FromStr for Channels {
+  type Err = String;
+
+  fn from_str(name: &str) -> Result<Self, Self::Err> {
+    match name {
+      "all" => Ok(Channels::All),
+      "red" => Ok(Channels::Red),
+      "green" => Ok(Channels::Green),
+      "blue" => Ok(Channels::Blue),
+      _ => Err(format!("must be one of {}", Channels::NAMES.join(", "))),
+    }
+  }
+}
+
+/// A levels adjustment. Every value is between 0.0 and 1.0.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Levels {
+  /// The input value that becomes `out_black`. Anything darker is clipped.
+  pub black: f64,
+  /// The input value that becomes `out_white`. Anything lighter is clipped.
+  pub white: f64,
+  /// The midtone gamma. Above 1 brightens, below 1 darkens.
+  pub gamma: f64,
+  pub out_black: f64,
+  pub out_white: f64,
+}
+
+impl Levels {
+  /// Maps an input `value` to its output value.
+  ///
+  /// Arguments:
+  ///
+  /// * `value` - f64
+  ///
+  /// Usage: ```levels.apply(0.5);```
+  pub fn apply(&self, value: f64) -> f64 {
+    let t = ((value - self.black) / (self.white - self.black)).clamp(0.0, 1.0);
+
+    self.out_black + t.powf(1.0 / self.gamma) * (self.out_white - self.out_black)
+  }
+}
+
+impl Default for Levels {
+  fn default() -> Self {
+    Levels {
+      black: 0.0,
+      white: 1.0,
+      gamma: 1.0,
+      out_black: 0.0,
+      out_white: 1.0,
+    }
+  }
+}
+
+/// A tone curve through control points, interpolated with a monotone cubic spline so it never
+/// overshoots between points.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Curve {
+  /// `(input, output)` pairs between 0.0 and 1.0, sorted by input.
+  points: Vec<(f64, f64)>,
+  /// The slope of the curve at each point.
+  tangents: Vec<f64>,
+}
+
+impl Curve {
+  /// Creates a curve through at least two `(input, output)` points between 0.0 and 1.0, each with
+  /// a different input.
+  ///
+  /// Arguments:
+  ///
+  /// * `points` - Vec<(f64, f64)>
+  ///
+  /// Usage: ```Curve::new(vec![(0.0, 0.0), (0.5, 0.6), (1.0, 1.0)])?;```
+  pub fn new(mut points: Vec<(f64, f64)>) -> Result<Self, String> {
+    if points.len() < 2 {
+      return Err("needs at least two points".to_string());
+    }
+    let in_range = |value: f64| (0.0..=1.0).contains(&value);
+    if points.iter().any(|&(x, y)| !in_range(x) || !in_range(y)) {
+      return Err("has a point outside of 0.0 to 1.0".to_string());
+    }
+    points.sort_by(|a, b| a.0.partial_cmp(&b.0).expect("points are finite"));
+    if points.windows(2).any(|pair| pair[0].0 == pair[1].0) {
+      return Err("has two points with the same input".to_string());
+    }
+
+    // Fritsch-Carlson: start from the secant slopes, then limit the tangents so every segment
+    // stays monotone.
+    let secants: Vec<f64> = points
+      .windows(2)
+      .map(|pair| (pair[1].1 - pair[0].1) / (pair[1].0 - pair[0].0))
+      .collect();
+    let mut tangents = Vec::with_capacity(points.len());
+    tangents.push(secants[0]);
+    for pair in secants.windows(2) {
+      tangents.push(if pair[0] * pair[1] <= 0.0 {
+        0.0
+      } else {
+        (pair[0] + pair[1]) / 2.0
+      });
+    }
+    tangents.push(secants[secants.len() - 1]);
+    for (segment, &secant) in secants.iter().enumerate() {
+      if secant == 0.0 {
+        tangents[segment] = 0.0;
+        tangents[segment + 1] = 0.0;
+        continue;
+      }
+      let (a, b) = (tangents[segment] / secant, tangents[segment + 1] / secant);
+      let length = a.hypot(b);
+      if length > 3.0 {
+        tangents[segment] = 3.0 / length * a * secant;
+        tangents[segment + 1] = 3.0 / length * b * secant;
+      }
+    }
+
+    Ok(Curve { points, tangents })
+  }
+
+  /// Evaluates the curve at `x`. Inputs outside the first and last points stay flat.
+  ///
+  /// Arguments:
+  ///
+  /// * `x` - f64
+  ///
+  /// Usage: ```curve.apply(0.25);```
+  pub fn apply(&self, x: f64) -> f64 {
+    let (first, last) = (self.points[0], self.points[self.points.len() - 1]);
+    if x <= first.0 {
+      return first.1;
+    }
+    if x >= last.0 {
+      return last.1;
+    }
+
+    let segment = self
+      .points
+      .windows(2)
+      .position(|pair| x <= pair[1].0)
+      .unwrap_or(self.points.len() - 2);
+    let ((x0, y0), (x1, y1)) = (self.points[segment], self.points[segment + 1]);
+    let (m0, m1) = (self.tangents[segment], self.tangents[segment + 1]);
+    let width = x1 - x0;
+    let t = (x - x0) / width;
+    // Cubic Hermite basis functions.
+    let (t2, t3) = (t * t, t * t * t);
+    let h00 = 2.0 * t3 - 3.0 * t2 + 1.0;
+    let h10 = t3 - 2.0 * t2 + t;
+    let h01 = -2.0 * t3 + 3.0 * t2;
+    let h11 = t3 - t2;
+
+    h00 * y0 + h10 * width * m0 + h01 * y1 + h11 * width * m1
+  }
+}
+
+impl FromStr for Curve {
+  type Err = String;
+
+  /// Parses comma separated `input:output` points between 0 and 255, such as
+  /// `0:0,64:48,192:210,255:255`.
+  fn from_str(spec: &str) -> Result<Self, Self::Err> {
+    let invalid = || "must be points like 0:0,128:150,255:255".to_string();
+    let mut points = Vec::new();
+    for point in spec.split(',') {
+      let mut parts = point.splitn(2, ':');
+      let mut coordinate = || {
+        parts
+          .next()
+          .and_then(|value| value.trim().parse::<f64>().ok())
+          .filter(|value| value.is_finite())
+          .ok_or_else(invalid)
+      };
+      points.push((coordinate()?, coordinate()?));
+    }
+    if points
+      .iter()
+      .any(|&(x, y)| !(0.0..=255.0).contains(&x) || !(0.0..=255.0).contains(&y))
+    {
+      return Err("has a point outside of 0 to 255".to_string());
+    }
+    let points = points
+      .into_iter()
+      .map(|(x, y)| (x / 255.0, y / 255.0))
+      .collect();
+
+    Curve::new(points)
+  }
+}
+
+/// Raises or lowers contrast by `amount` percent around middle gray. -100 flattens the image to
+/// gray; 100 doubles the contrast.
+///
+/// Arguments:
+///
+/// * `img` - &DynamicImage
+/// * `amount` - f64
+///
+/// Usage: ```adjust::contrast(&img, 25.0);```
+pub fn contrast(img: &DynamicImage, amount: f64) -> DynamicImage {
+  let factor = (100.0 + amount) / 100.0;
+
+  map_colors(img, |rgb| rgb.map(|value| (value - 0.5) * factor + 0.5))
+}
+
+/// Raises or lowers saturation by `amount` percent. -100 removes all color.
+///
+/// Arguments:
+///
+/// * `img` - &DynamicImage
+/// * `amount` - f64
+///
+/// Usage: ```adjust::saturation(&img, -50.0);```
+pub fn saturation(img: &DynamicImage, amount: f64) -> DynamicImage {
+  let factor = (100.0 + amount) / 100.0;
+
+  map_colors(img, |rgb| {
+    let luma = luminance(rgb);
+    rgb.map(|value| luma + (value - luma) * factor)
+  })
+}
+
+/// Rotates every color's hue by `degrees` around the color wheel, keeping its saturation and
+/// value.
+///
+/// Arguments:
+///
+/// * `img` - &DynamicImage
+/// * `degrees` - f64
+///
+/// Usage: ```adjust::hue(&img, 120.0);```
+pub fn hue(img: &DynamicImage, degrees: f64) -> DynamicImage {
+  map_colors(img, |rgb| {
+    let (hue, saturation, value) = to_hsv(rgb);
+    from_hsv((hue + degrees).rem_euclid(360.0), saturation, value)
+  })
+}
+
+/// Applies gamma correction. Values above 1 brighten the midtones and values below 1 darken them;
+/// black and white stay put.
+///
+/// Arguments:
+///
+/// * `img` - &DynamicImage
+/// * `gamma` - f64
+///
+/// Usage: ```adjust::gamma(&img, 2.2);```
+pub fn gamma(img: &DynamicImage, gamma: f64) -> DynamicImage {
+  map_colors(img, |rgb| rgb.map(|value| value.max(0.0).powf(1.0 / gamma)))
+}
+
+/// Changes exposure by a number of photographic `stops`. Every stop doubles (or halves) the
+/// light, which is applied in linear light so midtones respond like a camera would.
+///
+/// Arguments:
+///
+/// * `img` - &DynamicImage
+/// * `stops` - f64
+///
+/// Usage: ```adjust::exposure(&img, -1.5);```
+pub fn exposure(img: &DynamicImage, stops: f64) -> DynamicImage {
+  let factor = 2f64.powf(stops);
+
+  map_colors(img, |rgb| {
+    rgb.map(|value| to_srgb(to_linear(value) * factor))
+  })
+}
+
+/// Applies `levels` to some of the color `channels`.
+///
+/// Arguments:
+///
+/// * `img` - &DynamicImage
+/// * `levels` - Levels
+/// * `channels` - Channels
+///
+/// Usage: ```adjust::levels(&img, Levels { black: 0.1, ..Levels::default() }, Channels::All);```
+pub fn levels(img: &DynamicImage, levels: Levels, channels: Channels) -> DynamicImage {
+  map_tones(img, channels, |value| levels.apply(value))
+}
+
+/// Applies a tone `curve` to some of the color `channels`.
+///
+/// Arguments:
+///
+/// * `img` - &DynamicImage
+/// * `curve` - &Curve
+/// * `channels` - Channels
+///
+/// Usage: ```adjust::curves(&img, &"0:0,128:160,255:255".parse()?, Channels::All);```
+pub fn curves(img: &DynamicImage, curve: &Curve, channels: Channels) -> DynamicImage {
+  map_tones(img, channels, |value| curve.apply(value))
+}
+
+/// Spreads the luminance of an image evenly over the whole tonal range, so every brightness is
+/// about as common as any other. Colors are kept.
+///
+/// Arguments:
+///
+/// * `img` - &DynamicImage
+///
+/// Usage: ```adjust::equalize(&img);```
+pub fn equalize(img: &DynamicImage) -> DynamicImage {
+  let lumas = luminances(img);
+  let mut counts = [0.0; EQUALIZE_BINS];
+  for &luma in &lumas {
+    counts[bin(luma)] += 1.0;
+  }
+  let table = equalization_table(&counts);
+  // Stretch the darkest tone down to black.
+  let darkest = counts
+    .iter()
+    .position(|&count| count > 0.0)
+    .map_or(0.0, |first| table[first]);
+  if darkest >= 1.0 {
+    return img.clone();
+  }
+
+  map_luminance(img, |_, _, luma| {
+    (lookup_table(&table, luma) - darkest) / (1.0 - darkest)
+  })
+}
+
+/// Contrast limited adaptive histogram equalization: equalizes the luminance of each of a
+/// `tiles` x `tiles` grid separately, blending smoothly between them, so local contrast is
+/// raised in both the shadows and the highlights. No tone grows more common than `clip_limit`
+/// times the average, which stops flat areas and noise from being blown up, and tiles of a single
+/// tone are left as they are. Colors are kept.
+///
+/// Arguments:
+///
+/// * `img` - &DynamicImage
+/// * `tiles` - u32
+/// * `clip_limit` - f64
+///
+/// Usage: ```adjust::clahe(&img, 8, 2.0);```
+pub fn clahe(img: &DynamicImage, tiles: u32, clip_limit: f64) -> DynamicImage {
+  let (width, height) = img.dimensions();
+  let lumas = luminances(img);
+  let (tiles_x, tiles_y) = (tiles.clamp(1, width.max(1)), tiles.clamp(1, height.max(1)));
+  // Where tile `index` of `count` starts along a side `length` pixels long.
+  let edge = |index: u32, count: u32, length: u32| {
+    (u64::from(index) * u64::from(length) / u64::from(count)) as u32
+  };
+
+  let mut tables = Vec::with_capacity((tiles_x * tiles_y) as usize);
+  for tile_y in 0..tiles_y {
+    for tile_x in 0..tiles_x {
+      let mut counts = [0.0; EQUALIZE_BINS];
+      for y in edge(tile_y, tiles_y, height)..edge(tile_y + 1, tiles_y, height) {
+        for x in edge(tile_x, tiles_x, width)..edge(tile_x + 1, tiles_x, width) {
+          counts[bin(lumas[y as usize * width as usize + x as usize])] += 1.0;
+        }
+      }
+
+      // A tile of a single tone has no contrast to raise, so it keeps its tones.
+      if counts.iter().filter(|&&count| count > 0.0).count() <= 1 {
+        tables.push(
+          (0..EQUALIZE_BINS)
+            .map(|bin| bin as f64 / (EQUALIZE_BINS - 1) as f64)
+            .collect(),
+        );
+        continue;
+      }
+
+      // Clip the counts and share what was cut off among every bin.
+      let limit = clip_limit * counts.iter().sum::<f64>() / EQUALIZE_BINS as f64;
+      let excess: f64 = counts.iter().map(|&count| (count - limit).max(0.0)).sum();
+      for count in counts.iter_mut() {
+        *count = count.min(limit) + excess / EQUALIZE_BINS as f64;
+      }
+      tables.push(equalization_table(&counts));
+    }
+  }
+
+  // Where a pixel falls between the middles of the tiles along one side, as the tiles on
+  // either side and how far it is from the first to the second.
+  let between = |position: u32, count: u32, length: u32| {
+    let size = f64::from(length) / f64::from(count);
+    let at = ((f64::from(position) + 0.5) / size - 0.5).clamp(0.0, f64::from(count - 1));
+    let first = at.floor() as u32;
+    (first, (first + 1).min(count - 1), at - f64::from(first))
+  };
+
+  map_luminance(img, |x, y, luma| {
+    let (left, right, across) = between(x, tiles_x, width);
+    let (top, bottom, down) = between(y, tiles_y, height);
+    let tone =
+      |tile_x: u32, tile_y: u32| lookup_table(&tables[(tile_y * tiles_x + tile_x) as usize], luma);
+    let upper = tone(left, top) + (tone(right, top) - tone(left, top)) * across;
+    let lower = tone(left, bottom) + (tone(right, bottom) - tone(left, bottom)) * across;
+
+    upper + (lower - upper) * down
+  })
+}
+
+/// The luminance of every pixel, between 0.0 and 1.0, row by row.
+fn luminances(img: &DynamicImage) -> Vec<f64> {
+  img
+    .to_rgba16()
+    .pixels()
+    .map(|pixel| {
+      let [red, green, blue, _] = pixel.0;
+      luminance([red, green, blue].map(|channel| f64::from(channel) / 65535.0))
+    })
+    .collect()
+}
+
+/// The equalization bin a luminance falls in.
+fn bin(luma: f64) -> usize {
+  (luma.clamp(0.0, 1.0) * (EQUALIZE_BINS - 1) as f64).round() as usize
+}
+
+/// Maps every bin of a histogram to the share of the counts in it and the bins below it.
+fn equalization_table(counts: &[f64]) -> Vec<f64> {
+  let total: f64 = counts.iter().sum();
+  let mut seen = 0.0;
+  counts
+    .iter()
+    .map(|count| {
+      seen += count;
+      if total > 0.0 {
+        seen / total
+      } else {
+        0.0
+      }
+    })
+    .collect()
+}
+
+/// Looks up a luminance in an equalization table, interpolating between bins so 16-bit images
+/// don't band.
+fn lookup_table(table: &[f64], luma: f64) -> f64 {
+  let at = luma.clamp(0.0, 1.0) * (table.len() - 1) as f64;
+  let below = at.floor() as usize;
+  let above = (below + 1).min(table.len() - 1);
+
+  table[below] + (table[above] - table[below]) * (at - below as f64)
+}
+
+/// Maps the luminance of every pixel (x, y) through `tone`, which takes and returns values
+/// between 0.0 and 1.0. The change is added to all three channels, which keeps the chroma of
+/// every pixel, as in YCbCr.
+fn map_luminance(img: &DynamicImage, tone: impl Fn(u32, u32, f64) -> f64) -> DynamicImage {
+  map_pixels(img, |x, y, rgb| {
+    let luma = luminance(rgb);
+    let shift = tone(x, y, luma) - luma;
+
+    rgb.map(|channel| channel + shift)
+  })
+}
+
+/// Maps every value of some color `channels` through `tone`, which takes and returns values
+/// between 0.0 and 1.0. `tone` is only evaluated once per possible value, into a lookup table at
+/// the image's bit depth.
+///
+/// Arguments:
+///
+/// * `img` - &DynamicImage
+/// * `channels` - Channels
+/// * `tone` - impl Fn(f64) -> f64
+///
+/// Usage: ```adjust::map_tones(&img, Channels::Red, |value| value * value);```
+pub fn map_tones(
+  img: &DynamicImage,
+  channels: Channels,
+  tone: impl Fn(f64) -> f64,
+) -> DynamicImage {
+  let adjusted = if is_16_bit(img) {
+    DynamicImage::ImageRgba16(lookup(&img.to_rgba16(), channels, tone))
+  } else {
+    DynamicImage::ImageRgba8(lookup(&img.to_rgba8(), channels, tone))
+  };
+
+  with_color_type(adjusted, img)
+}
+
+fn lookup<S: Channel>(
+  src: &ImageBuffer<Rgba<S>, Vec<S>>,
+  channels: Channels,
+  tone: impl Fn(f64) -> f64,
+) -> ImageBuffer<Rgba<S>, Vec<S>> {
+  let table: Vec<S> = (0..=S::MAX_VALUE as usize)
+    .map(|value| S::from_f64(tone(value as f64 / S::MAX_VALUE) * S::MAX_VALUE))
+    .collect();
+  let mask = channels.mask();
+
+  let mut out = src.clone();
+  for pixel in out.pixels_mut() {
+    for (channel, &included) in pixel.0.iter_mut().zip(mask.iter()) {
+      if included {
+        let value: f64 = (*channel).into();
+        *channel = table[value as usize];
+      }
+    }
+  }
+
+  out
+}
+
+/// Runs `adjust` over the red, green and blue of every pixel, scaled to between 0.0 and 1.0, at
+/// the image's own bit depth. Alpha is left alone, results are clamped and the image keeps its
+/// color type.
+///
+/// Arguments:
+///
+/// * `img` - &DynamicImage
+/// * `adjust` - impl Fn([f64; 3]) -> [f64; 3]
+///
+/// Usage: ```adjust::map_colors(&img, |[r, g, b]| [b, g, r]);```
+pub fn map_colors(img: &DynamicImage, adjust: impl Fn([f64; 3]) -> [f64; 3]) -> DynamicImage {
+  map_pixels(img, |_, _, rgb| adjust(rgb))
+}
+
+/// Like `map_colors`, but `adjust` is also passed the position of every pixel.
+fn map_pixels(img: &DynamicImage, adjust: impl Fn(u32, u32, [f64; 3]) -> [f64; 3]) -> DynamicImage {
+  let adjusted = if is_16_bit(img) {
+    DynamicImage::ImageRgba16(map_rgba(&img.to_rgba16(), adjust))
+  } else {
+    DynamicImage::ImageRgba8(map_rgba(&img.to_rgba8(), adjust))
+  };
+
+  with_color_type(adjusted, img)
+}
+
+fn map_rgba<S: Channel>(
+  src: &ImageBuffer<Rgba<S>, Vec<S>>,
+  adjust: impl Fn(u32, u32, [f64; 3]) -> [f64; 3],
+) -> ImageBuffer<Rgba<S>, Vec<S>> {
+  let mut out = src.clone();
+  for (x, y, pixel) in out.enumerate_pixels_mut() {
+    let mut rgb = [0.0; 3];
+    for (value, channel) in rgb.iter_mut().zip(pixel.0.iter()) {
+      *value = (*channel).into() / S::MAX_VALUE;
+    }
+    for (channel, value) in pixel.0.iter_mut().zip(adjust(x, y, rgb).iter()) {
+      *channel = S::from_f64(value * S::MAX_VALUE);
+    }
+  }
+
+  out
+}
+
+/// The Rec. 709 luminance of a color.
+pub(crate) fn luminance([red, green, blue]: [f64; 3]) -> f64 {
+  0.2126 * red + 0.7152 * green + 0.0722 * blue
+}
+
+/// Converts an sRGB value to linear light.
+fn to_linear(value: f64) -> f64 {
+  if value <= 0.04045 {
+    value / 12.92
+  } else {
+    ((value + 0.055) / 1.055).powf(2.4)
+  }
+}
+
+/// Converts linear light to an sRGB value.
+fn to_srgb(value: f64) -> f64 {
+  if value <= 0.0031308 {
+    value * 12.92
+  } else {
+    1.055 * value.powf(1.0 / 2.4) - 0.055
+  }
+}
+
+/// Converts a color to hue in degrees, saturation and value.
+fn to_hsv([red, green, blue]: [f64; 3]) -> (f64, f64, f64) {
+  let max = red.max(green).max(blue);
+  let min = red.min(green).min(blue);
+  let chroma = max - min;
+  let hue = if chroma <= 0.0 {
+    0.0
+  } else if max == red {
+    60.0 * ((green - blue) / chroma).rem_euclid(6.0)
+  } else if max == green {
+    60.0 * ((blue - red) / chroma + 2.0)
+  } else {
+    60.0 * ((red - green) / chroma + 4.0)
+  };
+  let saturation = if max > 0.0 { chroma / max } else { 0.0 };
+
+  (hue, saturation, max)
+}
+
+/// Converts hue in degrees, saturation and value back to a color.
+fn from_hsv(hue: f64, saturation: f64, value: f64) -> [f64; 3] {
+  let chroma = value * saturation;
+  let sector = hue / 60.0;
+  let x = chroma * (1.0 - (sector.rem_euclid(2.0) - 1.0).abs());
+  let (red, green, blue) = match sector as u32 {
+    0 => (chroma, x, 0.0),
+    1 => (x, chroma, 0.0),
+    2 => (0.0, chroma, x),
+    3 => (0.0, x, chroma),
+    4 => (x, 0.0, chroma),
+    _ => (chroma, 0.0, x),
+  };
+  let lift = value - chroma;
+
+  [red + lift, green + lift, blue + lift]
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use image::ColorType;
+
+  fn translucent() -> DynamicImage {
+    DynamicImage::ImageRgba16(ImageBuffer::from_fn(4, 3, |x, y| {
+      let level = (x * 3 + y) as u16 * 5000;
+      Rgba([level, 60000 - level, 30000, 1000 + level])
+    }))
+  }
+
+  fn alphas(img: &DynamicImage) -> Vec<u16> {
+    img.to_rgba16().pixels().map(|pixel| pixel[3]).collect()
+  }
+
+  #[test]
+  fn adjustments_keep_16_bit_color_and_alpha() {
+    let img = translucent();
+    let adjusted = [
+      contrast(&img, 40.0),
+      saturation(&img, -60.0),
+      hue(&img, 90.0),
+      gamma(&img, 2.2),
+      exposure(&img, 1.0),
+    ];
+
+    for result in adjusted.iter() {
+      assert_eq!(result.color(), ColorType::Rgba16);
+      assert_eq!(alphas(result), alphas(&img));
+      assert_ne!(result.to_rgba16(), img.to_rgba16());
+    }
+  }
+
+  #[test]
+  fn neutral_adjustments_round_trip_every_16_bit_value() {
+    let img = translucent();
+
+    for result in [
+      contrast(&img, 0.0),
+      saturation(&img, 0.0),
+      hue(&img, 360.0),
+      gamma(&img, 1.0),
+      exposure(&img, 0.0),
+    ]
+    .iter()
+    {
+      let (before, after) = (img.to_rgba16(), result.to_rgba16());
+      for (a, b) in before.pixels().zip(after.pixels()) {
+        for (x, y) in a.0.iter().zip(b.0.iter()) {
+          assert!(
+            (i32::from(*x) - i32::from(*y)).abs() <= 1,
+            "{:?} {:?}",
+            a,
+            b
+          );
+        }
+      }
+    }
+  }
+
+  #[test]
+  fn hue_turns_primaries_around_the_wheel() {
+    let red = DynamicImage::ImageRgb8(image::RgbImage::from_pixel(1, 1, image::Rgb([255, 0, 0])));
+
+    assert_eq!(hue(&red, 120.0).to_rgb8().get_pixel(0, 0).0, [0, 255, 0]);
+    assert_eq!(hue(&red, -120.0).to_rgb8().get_pixel(0, 0).0, [0, 0, 255]);
+    assert_eq!(
+      saturation(&red, -100.0).to_rgb8().get_pixel(0, 0).0,
+      [54, 54, 54]
+    );
+  }
+
+  #[test]
+  fn curves_need_two_distinct_points_in_range() {
+    assert!(Curve::new(vec![(0.5, 0.5)]).is_err());
+    assert!(Curve::new(vec![(0.0, 0.0), (1.5, 1.0)]).is_err());
+    assert!(Curve::new(vec![(0.0, -0.1), (1.0, 1.0)]).is_err());
+    assert!(Curve::new(vec![(0.0, 0.0), (0.5, 0.2), (0.5, 0.8)]).is_err());
+  }
+
+  #[test]
+  fn curves_pass_through_their_points_in_any_order() {
+    let points = vec![(1.0, 1.0), (0.25, 0.4), (0.0, 0.1), (0.75, 0.6)];
+    let curve = Curve::new(points.clone()).unwrap();
+
+    for (x, y) in points {
+      assert!((curve.apply(x) - y).abs() < 1e-12, "{} -> {}", x, y);
+    }
+    assert_eq!(curve.apply(-1.0), 0.1);
+    assert_eq!(curve.apply(2.0), 1.0);
+  }
+
+  #[test]
+  fn curves_through_rising_points_never_fall() {
+    let curve = Curve::new(vec![(0.0, 0.0), (0.1, 0.8), (0.2, 0.81), (1.0, 1.0)]).unwrap();
+    let samples: Vec<f64> = (0..=1000)
+      .map(|i| curve.apply(f64::from(i) / 1000.0))
+      .collect();
+
+    assert!(samples.windows(2).all(|pair| pair[1] >= pair[0]));
+    assert!(samples.iter().all(|&y| (0.0..=1.0).contains(&y)));
+  }
+
+  #[test]
+  fn curve_specs_are_points_from_0_to_255() {
+    let curve: Curve = "0:0,128:192,255:255".parse().unwrap();
+
+    assert!((curve.apply(128.0 / 255.0) - 192.0 / 255.0).abs() < 1e-12);
+    assert_eq!(
+      "0:0,256:255".parse::<Curve>().err().unwrap(),
+      "has a point outside of 0 to 255"
+    );
+    assert!("0:0,128".parse::<Curve>().is_err());
+    assert!("0:0;255:255".parse::<Curve>().is_err());
+  }
+
+  #[test]
+  fn levels_clip_stretch_and_remap_the_input_range() {
+    let levels = Levels {
+      black: 0.2,
+      white: 0.6,
+      out_black: 0.1,
+      out_white: 0.9,
+      ..Levels::default()
+    };
+
+    assert_eq!(levels.apply(0.0), 0.1);
+    assert_eq!(levels.apply(1.0), 0.9);
+    assert!((levels.apply(0.4) - 0.5).abs() < 1e-12);
+    assert_eq!(Levels::default().apply(0.3), 0.3);
+  }
+
+  fn gray(width: u32, level: impl Fn(u32, u32) -> u8) -> DynamicImage {
+    DynamicImage::ImageLuma8(image::GrayImage::from_fn(width, width, |x, y| {
+      image::Luma([level(x, y)])
+    }))
+  }
+
+  #[test]
+  fn equalizing_two_levels_stretches_them_to_black_and_white() {
+    let img = gray(8, |x, _| if x < 4 { 100 } else { 150 });
+    let equalized = equalize(&img).to_luma8();
+
+    assert_eq!(equalized.get_pixel(0, 0)[0], 0);
+    assert_eq!(equalized.get_pixel(7, 7)[0], 255);
+    assert!(equalized
+      .pixels()
+      .all(|pixel| pixel[0] == 0 || pixel[0] == 255));
+  }
+
+  #[test]
+  fn clahe_leaves_a_flat_image_alone() {
+    for &level in [0, 40, 128, 200, 255].iter() {
+      let img = gray(16, |_, _| level);
+      for &(tiles, clip_limit) in [(1, 1.0), (4, 2.0), (8, 40.0)].iter() {
+        assert_eq!(
+          clahe(&img, tiles, clip_limit).to_luma8(),
+          img.to_luma8(),
+          "level {}, {} tiles, clip limit {}",
+          level,
+          tiles,
+          clip_limit
+        );
+      }
+    }
+  }
+}
@@ -0,0 +1,331 @@
+use crate::encode::SaveOptions;
+use crate::op::{ImageOp, OpArgs};
+use crate::{open_image, resolve_threads, save_image_with, ImageUtilsError, Result};
+use crossbeam::channel;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// The result of running the stages over a single file.
+#[derive(Debug)]
+pub struct FileOutcome {
+  pub input: PathBuf,
+  pub output: PathBuf,
+  pub result: Result<()>,
+}
+
+/// How many files a batch processed successfully and how many failed.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct BatchSummary {
+  pub succeeded: usize,
+  pub failed: usize,
+}
+
+/// Resolves a batch input into the image files it refers to. The input can be a directory (every
+/// image in it), a glob pattern in the final path component (`photos/*.png`) or a single file. A
+/// directory or glob that matches nothing is an error.
+///
+/// Arguments:
+///
+/// * `input` - &str
+///
+/// Usage: ```batch::find_inputs("photos/*.png")?;```
+pub fn find_inputs(input: &str) -> Result<Vec<PathBuf>> {
+  let path = Path::new(input);
+  let is_glob = |name: &str| name.contains('*') || name.contains('?');
+
+  let (dir, pattern) = match path.file_name().and_then(|name| name.to_str()) {
+    Some(name) if is_glob(name) => (
+      path
+        .parent()
+        .filter(|parent| !parent.as_os_str().is_empty())
+        .unwrap_or_else(|| Path::new(".")),
+      Some(name),
+    ),
+    _ if path.is_dir() => (path, None),
+    _ if path.exists() => return Ok(vec![path.to_path_buf()]),
+    _ => {
+      return Err(ImageUtilsError::bad_argument(
+        "batch",
+        "input",
+        format!("{} doesn't exist", input),
+      ))
+    }
+  };
+
+  let entries = fs::read_dir(dir).map_err(|source| ImageUtilsError::Io {
+    path: dir.display().to_string(),
+    source,
+  })?;
+
+  let mut inputs = Vec::new();
+  for entry in entries {
+    let entry = entry.map_err(|source| ImageUtilsError::Io {
+      path: dir.display().to_string(),
+      source,
+    })?;
+    let file = entry.path();
+    let name = entry.file_name();
+    let name = name.to_string_lossy();
+    let matches = match pattern {
+      Some(pattern) => glob_match(pattern, &name),
+      None => image::ImageFormat::from_path(&file).is_ok(),
+    };
+    if matches && file.is_file() {
+      inputs.push(file);
+    }
+  }
+  inputs.sort();
+
+  if inputs.is_empty() {
+    return Err(ImageUtilsError::bad_argument(
+      "batch",
+      "input",
+      format!("{} matched no image files", input),
+    ));
+  }
+
+  Ok(inputs)
+}
+
+/// Matches a file `name` against a `pattern` where `*` matches any run of characters and `?`
+/// matches a single character.
+///
+/// Arguments:
+///
+/// * `pattern` - &str
+/// * `name` - &str
+///
+/// Usage: ```glob_match("*.png", "dyson.png");```
+pub fn glob_match(pattern: &str, name: &str) -> bool {
+  let pattern: Vec<char> = pattern.chars().collect();
+  let name: Vec<char> = name.chars().collect();
+  let (mut p, mut n) = (0, 0);
+  let mut backtrack: Option<(usize, usize)> = None;
+
+  while n < name.len() {
+    match pattern.get(p) {
+      Some('*') => {
+        backtrack = Some((p, n));
+        p += 1;
+      }
+      Some(&c) if c == '?' || c == name[n] => {
+        p += 1;
+        n += 1;
+      }
+      _ => match backtrack {
+        // Let the last `*` swallow one more character and try again.
+        Some((star, matched)) => {
+          backtrack = Some((star, matched + 1));
+          p = star + 1;
+          n = matched + 1;
+        }
+        None => return false,
+      },
+    }
+  }
+
+  pattern[p..].iter().all(|&c| c == '*')
+}
+
+/// Runs `stages` over every input on a pool of `threads` workers, writing each result into
+/// `output_dir` under the input's file name, encoded with `options`. A file that fails doesn't
+/// stop the batch; every outcome is passed to `report` as it finishes.
+///
+/// Nothing is processed if `output_dir` holds any of the inputs, which would be overwritten, or if
+/// two inputs would be written to the same output, such as `a.png` and `a.jpg` with a forced
+/// format.
+///
+/// Arguments:
+///
+/// * `stages` - &[(&dyn ImageOp, OpArgs)]
+/// * `inputs` - Vec<PathBuf>
+/// * `output_dir` - &Path
+/// * `threads` - usize (0 uses every core)
+/// * `options` - &SaveOptions
+/// * `report` - impl FnMut(&FileOutcome)
+///
+/// Usage: ```batch::process(&stages, inputs, Path::new("out"), 4, &SaveOptions::default(), |outcome| println!("{:?}", outcome))?;```
+pub fn process(
+  stages: &[(&dyn ImageOp, OpArgs)],
+  inputs: Vec<PathBuf>,
+  output_dir: &Path,
+  threads: usize,
+  options: &SaveOptions,
+  mut report: impl FnMut(&FileOutcome),
+) -> Result<BatchSummary> {
+  let jobs = plan_outputs(inputs, output_dir, options)?;
+  fs::create_dir_all(output_dir).map_err(|source| ImageUtilsError::Io {
+    path: output_dir.display().to_string(),
+    source,
+  })?;
+
+  let threads = resolve_threads(threads).min(jobs.len().max(1));
+
+  let (job_tx, job_rx) = channel::unbounded::<(PathBuf, PathBuf)>();
+  let (outcome_tx, outcome_rx) = channel::unbounded::<FileOutcome>();
+  for job in jobs {
+    job_tx.send(job).expect("the job channel is open");
+  }
+  // Closing the sending side lets workers exit once the queue is drained.
+  drop(job_tx);
+
+  let mut summary = BatchSummary::default();
+  crossbeam::scope(|scope| {
+    for _ in 0..threads {
+      let job_rx = job_rx.clone();
+      let outcome_tx = outcome_tx.clone();
+      scope.spawn(move |_| {
+        for (input, output) in job_rx {
+          let result = process_file(stages, &input, &output, options);
+          let _ = outcome_tx.send(FileOutcome {
+            input,
+            output,
+            result,
+          });
+        }
+      });
+    }
+    // Drop our copy so the outcome channel closes once every worker exits.
+    drop(outcome_tx);
+
+    for outcome in outcome_rx {
+      match outcome.result {
+        Ok(()) => summary.succeeded += 1,
+        Err(_) => summary.failed += 1,
+      }
+      report(&outcome);
+    }
+  })
+  .expect("a batch worker panicked");
+
+  Ok(summary)
+}
+
+/// Pairs every input with the path its result is written to, refusing to overwrite an input or to
+/// write two results to the same path.
+fn plan_outputs(
+  inputs: Vec<PathBuf>,
+  output_dir: &Path,
+  options: &SaveOptions,
+) -> Result<Vec<(PathBuf, PathBuf)>> {
+  // An output directory that doesn't exist yet can't hold any inputs.
+  let output_dir_canonical = fs::canonicalize(output_dir).ok();
+  let mut written: HashMap<PathBuf, PathBuf> = HashMap::new();
+  let mut jobs = Vec::with_capacity(inputs.len());
+
+  for input in inputs {
+    let input_dir = input
+      .parent()
+      .filter(|parent| !parent.as_os_str().is_empty())
+      .unwrap_or_else(|| Path::new("."));
+    if output_dir_canonical.is_some() && fs::canonicalize(input_dir).ok() == output_dir_canonical {
+      return Err(ImageUtilsError::bad_argument(
+        "batch",
+        "output",
+        format!(
+          "is the directory of the input {}, which would be overwritten",
+          input.display()
+        ),
+      ));
+    }
+
+    let mut output = output_dir.join(input.file_name().unwrap_or_default());
+    // A forced format gets its own extension.
+    if let Some(format) = options.format {
+      output.set_extension(format.extensions_str()[0]);
+    }
+    if let Some(other) = written.insert(output.clone(), input.clone()) {
+      return Err(ImageUtilsError::bad_argument(
+        "batch",
+        "output",
+        format!(
+          "would get both {} and {} as {}",
+          other.display(),
+          input.display(),
+          output.display()
+        ),
+      ));
+    }
+
+    jobs.push((input, output));
+  }
+
+  Ok(jobs)
+}
+
+/// Decodes `input`, runs every stage over it and encodes the result to `output` with `options`.
+fn process_file(
+  stages: &[(&dyn ImageOp, OpArgs)],
+  input: &Path,
+  output: &Path,
+  options: &SaveOptions,
+) -> Result<()> {
+  let mut img = open_image(input.display().to_string())?;
+  for (op, op_args) in stages {
+    img = op.apply(&img, op_args)?;
+  }
+
+  save_image_with(img, output.display().to_string(), options)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn globs_match_runs_and_single_characters() {
+    assert!(glob_match("*.png", "dyson.png"));
+    assert!(glob_match("*.png", ".png"));
+    assert!(glob_match("img_??.jpg", "img_01.jpg"));
+    assert!(glob_match("*a*b*", "xxaxxbxx"));
+    assert!(glob_match("**", ""));
+    assert!(glob_match("photo", "photo"));
+  }
+
+  #[test]
+  fn globs_reject_names_that_do_not_fit() {
+    assert!(!glob_match("*.png", "dyson.png.bak"));
+    assert!(!glob_match("img_??.jpg", "img_1.jpg"));
+    assert!(!glob_match("*a*b", "xxbxxa"));
+    assert!(!glob_match("?", ""));
+    assert!(!glob_match("photo", "Photo"));
+  }
+
+  #[test]
+  fn missing_and_empty_inputs_are_errors() {
+    assert!(find_inputs("src/missing.png").is_err());
+    assert!(find_inputs("src/*.nothing").is_err());
+    assert_eq!(
+      find_inputs("src/batch.rs").unwrap(),
+      vec![PathBuf::from("src/batch.rs")]
+    );
+  }
+
+  #[test]
+  fn outputs_are_never_inputs_or_shared() {
+    let plan = |inputs: &[&str], output_dir: &str, options: &SaveOptions| {
+      plan_outputs(
+        inputs.iter().map(PathBuf::from).collect(),
+        Path::new(output_dir),
+        options,
+      )
+    };
+    let forced_png = SaveOptions {
+      format: Some(image::ImageFormat::Png),
+      ..SaveOptions::default()
+    };
+
+    assert_eq!(
+      plan(&["a/x.png", "b/y.png"], "out", &SaveOptions::default()).unwrap(),
+      vec![
+        (PathBuf::from("a/x.png"), PathBuf::from("out/x.png")),
+        (PathBuf::from("b/y.png"), PathBuf::from("out/y.png")),
+      ]
+    );
+    assert!(plan(&["a/x.png", "b/x.png"], "out", &SaveOptions::default()).is_err());
+    assert!(plan(&["x.png", "x.jpg"], "out", &forced_png).is_err());
+    assert!(plan(&["src/x.png"], "src", &SaveOptions::default()).is_err());
+    assert!(plan(&["x.png"], ".", &SaveOptions::default()).is_err());
+  }
+}
@@ -0,0 +1,416 @@
+use crate::adjust::luminance;
+use image::{DynamicImage, GrayImage, ImageBuffer, Luma};
+use std::fmt;
+use std::str::FromStr;
+
+/// The edge detectors `detect` can run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operator {
+  /// The gradient magnitude from 3x3 Sobel kernels, which weight the middle row and column.
+  Sobel,
+  /// The gradient magnitude from 3x3 Prewitt kernels, which weight every neighbor equally.
+  Prewitt,
+  /// The Laplacian of a gaussian blur. Edges lie where it crosses zero.
+  Log,
+  /// Thin, connected edges from the Canny detector.
+  Canny,
+}
+
+impl Operator {
+  /// The names `Operator::from_str` accepts.
+  pub const NAMES: [&'static str; 4] = ["sobel", "prewitt", "log", "canny"];
+}
+
+impl FromStr for Operator {
+  type Err = String;
+
+  fn from_str(name: &str) -> Result<Self, Self::Err> {
+    match name {
+      "sobel" => Ok(Operator::Sobel),
+      "prewitt" => Ok(Operator::Prewitt),
+      "log" => Ok(Operator::Log),
+      "canny" => Ok(Operator::Canny),
+      _ => Err(format!("must be one of {}", Operator::NAMES.join(", "))),
+    }
+  }
+}
+
+impl fmt::Display for Operator {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      Operator::Sobel => write!(f, "sobel"),
+      Operator::Prewitt => write!(f, "prewitt"),
+      Operator::Log => write!(f, "log"),
+      Operator::Canny => write!(f, "canny"),
+    }
+  }
+}
+
+/// Settings for edge detection. Thresholds are on a 0 to 255 scale, where 255 is the strength of
+/// a sharp step from black to white.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct EdgeParams {
+  pub operator: Operator,
+  /// The standard deviation of the gaussian blur `Log` and `Canny` smooth the image with first.
+  pub sigma: f64,
+  /// Turns the grayscale edge strength of `Sobel`, `Prewitt` and `Log` into a black and white
+  /// map, keeping edges at least this strong. 0 keeps the grayscale map.
+  pub threshold: f64,
+  /// Canny edges weaker than this are dropped.
+  pub low: f64,
+  /// Canny edges at least this strong are kept, along with any weaker ones connected to them.
+  pub high: f64,
+}
+
+impl Default for EdgeParams {
+  fn default() -> Self {
+    EdgeParams {
+      operator: Operator::Sobel,
+      sigma: 1.4,
+      threshold: 0.0,
+      low: 20.0,
+      high: 50.0,
+    }
+  }
+}
+
+/// Finds the edges in an image's luminance, returning a grayscale map of their strength or a black
+/// and white map of where they are.
+///
+/// Arguments:
+///
+/// * `img` - &DynamicImage
+/// * `params` - EdgeParams
+///
+/// Usage: ```edge::detect(&img, EdgeParams { operator: Operator::Canny, ..EdgeParams::default() });```
+pub fn detect(img: &DynamicImage, params: EdgeParams) -> DynamicImage {
+  let plane = Plane::luminance(img);
+  let threshold = params.threshold / 255.0;
+
+  let map = match params.operator {
+    Operator::Sobel | Operator::Prewitt => {
+      let (magnitude, _) = gradients(&plane, params.operator == Operator::Sobel);
+      if threshold > 0.0 {
+        magnitude.to_binary(|_, _, value| value >= threshold)
+      } else {
+        magnitude.to_gray()
+      }
+    }
+    Operator::Log => {
+      let response = laplacian(&plane.blur(params.sigma));
+      if threshold > 0.0 {
+        // An edge pixel changes sign against a neighbor, with a strong enough swing.
+        response.to_binary(|x, y, value| {
+          [(1, 0), (0, 1)].iter().any(|&(dx, dy)| {
+            let next = response.get(x as i64 + dx, y as i64 + dy);
+            value * next < 0.0 && (value - next).abs() >= threshold
+          })
+        })
+      } else {
+        let strongest = response
+          .values
+          .iter()
+          .fold(0.0f64, |strongest, value| strongest.max(value.abs()));
+        response
+          .map(|value| {
+            if strongest > 0.0 {
+              value.abs() / strongest
+            } else {
+              0.0
+            }
+          })
+          .to_gray()
+      }
+    }
+    Operator::Canny => canny(
+      &plane.blur(params.sigma),
+      params.low / 255.0,
+      params.high / 255.0,
+    ),
+  };
+
+  DynamicImage::ImageLuma8(map)
+}
+
+/// Thin edges with hysteresis: the gradient magnitude is thinned to ridges one pixel wide,
+/// ridges stronger than `high` become edges and weaker ridges above `low` join them if they touch.
+fn canny(plane: &Plane, low: f64, high: f64) -> GrayImage {
+  let (magnitude, direction) = gradients(plane, true);
+  let (width, height) = (plane.width as i64, plane.height as i64);
+
+  // Non-maximum suppression: keep a pixel only if it's the peak across the edge.
+  let thin = Plane {
+    values: (0..plane.values.len())
+      .map(|index| {
+        let (x, y) = ((index as i64) % width, (index as i64) / width);
+        let value = magnitude.values[index];
+        let angle = direction.values[index].to_degrees().rem_euclid(180.0);
+        let (dx, dy) = if !(22.5..157.5).contains(&angle) {
+          (1, 0)
+        } else if angle < 67.5 {
+          (1, 1)
+        } else if angle < 112.5 {
+          (0, 1)
+        } else {
+          (-1, 1)
+        };
+        // Ties across a sharp step go to the pixel after it, so the edge stays one pixel wide.
+        // There's no gradient past the edges, so ridges along the border are still peaks.
+        let peak = value > magnitude.get_or_zero(x + dx, y + dy)
+          && value >= magnitude.get_or_zero(x - dx, y - dy);
+        if peak {
+          value
+        } else {
+          0.0
+        }
+      })
+      .collect(),
+    ..magnitude
+  };
+
+  let mut edges = vec![false; thin.values.len()];
+  let mut pending: Vec<usize> = (0..thin.values.len())
+    .filter(|&index| thin.values[index] >= high)
+    .collect();
+  while let Some(index) = pending.pop() {
+    if edges[index] {
+      continue;
+    }
+    edges[index] = true;
+    let (x, y) = ((index as i64) % width, (index as i64) / width);
+    for dy in -1..=1 {
+      for dx in -1..=1 {
+        let (nx, ny) = (x + dx, y + dy);
+        if (0..width).contains(&nx) && (0..height).contains(&ny) {
+          let neighbor = (ny * width + nx) as usize;
+          if !edges[neighbor] && thin.values[neighbor] >= low {
+            pending.push(neighbor);
+          }
+        }
+      }
+    }
+  }
+
+  thin.to_binary(|x, y, _| edges[(y * thin.width + x) as usize])
+}
+
+/// The gradient magnitude and direction (in radians) of a plane, from Sobel or Prewitt kernels.
+/// Both are scaled so a sharp step from 0 to 1 has a magnitude of 1.
+fn gradients(plane: &Plane, sobel: bool) -> (Plane, Plane) {
+  let (side, scale) = if sobel { (2.0, 4.0) } else { (1.0, 3.0) };
+  let mut magnitude = Vec::with_capacity(plane.values.len());
+  let mut direction = Vec::with_capacity(plane.values.len());
+  for y in 0..plane.height as i64 {
+    for x in 0..plane.width as i64 {
+      let at = |dx: i64, dy: i64| plane.get(x + dx, y + dy);
+      let gx = (at(1, -1) + side * at(1, 0) + at(1, 1) - at(-1, -1) - side * at(-1, 0) - at(-1, 1))
+        / scale;
+      let gy = (at(-1, 1) + side * at(0, 1) + at(1, 1) - at(-1, -1) - side * at(0, -1) - at(1, -1))
+        / scale;
+      magnitude.push(gx.hypot(gy));
+      direction.push(gy.atan2(gx));
+    }
+  }
+
+  (
+    Plane {
+      values: magnitude,
+      ..*plane
+    },
+    Plane {
+      values: direction,
+      ..*plane
+    },
+  )
+}
+
+/// The 4-neighbor Laplacian of a plane.
+fn laplacian(plane: &Plane) -> Plane {
+  let mut values = Vec::with_capacity(plane.values.len());
+  for y in 0..plane.height as i64 {
+    for x in 0..plane.width as i64 {
+      let at = |dx: i64, dy: i64| plane.get(x + dx, y + dy);
+      values.push(at(-1, 0) + at(1, 0) + at(0, -1) + at(0, 1) - 4.0 * at(0, 0));
+    }
+  }
+
+  Plane { values, ..*plane }
+}
+
+/// A single channel of `f64` values, row by row.
+#[derive(Debug, Clone, PartialEq)]
+struct Plane {
+  width: u32,
+  height: u32,
+  values: Vec<f64>,
+}
+
+impl Plane {
+  /// The luminance of an image, between 0.0 and 1.0.
+  fn luminance(img: &DynamicImage) -> Plane {
+    let rgba = img.to_rgba16();
+    let values = rgba
+      .pixels()
+      .map(|pixel| {
+        let [red, green, blue, _] = pixel.0;
+        luminance([red, green, blue].map(|channel| f64::from(channel) / 65535.0))
+      })
+      .collect();
+
+    Plane {
+      width: rgba.width(),
+      height: rgba.height(),
+      values,
+    }
+  }
+
+  /// The value at (`x`, `y`), repeating the edge pixels past the edges.
+  fn get(&self, x: i64, y: i64) -> f64 {
+    let x = x.clamp(0, i64::from(self.width) - 1);
+    let y = y.clamp(0, i64::from(self.height) - 1);
+
+    self.values[(y * i64::from(self.width) + x) as usize]
+  }
+
+  /// The value at (`x`, `y`), or 0.0 past the edges.
+  fn get_or_zero(&self, x: i64, y: i64) -> f64 {
+    if (0..i64::from(self.width)).contains(&x) && (0..i64::from(self.height)).contains(&y) {
+      self.values[(y * i64::from(self.width) + x) as usize]
+    } else {
+      0.0
+    }
+  }
+
+  fn map(&self, f: impl Fn(f64) -> f64) -> Plane {
+    Plane {
+      values: self.values.iter().map(|&value| f(value)).collect(),
+      ..*self
+    }
+  }
+
+  /// A separable gaussian blur with standard deviation `sigma`.
+  fn blur(&self, sigma: f64) -> Plane {
+    if sigma <= 0.0 {
+      return self.clone();
+    }
+    let reach = (sigma * 3.0).ceil() as i64;
+    let weights: Vec<f64> = (-reach..=reach)
+      .map(|offset| (-((offset * offset) as f64) / (2.0 * sigma * sigma)).exp())
+      .collect();
+    let total: f64 = weights.iter().sum();
+
+    let pass = |plane: &Plane, (dx, dy): (i64, i64)| {
+      let mut values = Vec::with_capacity(plane.values.len());
+      for y in 0..plane.height as i64 {
+        for x in 0..plane.width as i64 {
+          let sum: f64 = weights
+            .iter()
+            .zip(-reach..=reach)
+            .map(|(weight, offset)| weight * plane.get(x + offset * dx, y + offset * dy))
+            .sum();
+          values.push(sum / total);
+        }
+      }
+      Plane { values, ..*plane }
+    };
+
+    pass(&pass(self, (1, 0)), (0, 1))
+  }
+
+  /// Renders values between 0.0 and 1.0 as a grayscale image.
+  fn to_gray(&self) -> GrayImage {
+    ImageBuffer::from_fn(self.width, self.height, |x, y| {
+      let value = self.values[(y * self.width + x) as usize];
+      Luma([(value.clamp(0.0, 1.0) * 255.0).round() as u8])
+    })
+  }
+
+  /// Renders a black and white image, white wherever `is_edge` is true.
+  fn to_binary(&self, is_edge: impl Fn(u32, u32, f64) -> bool) -> GrayImage {
+    ImageBuffer::from_fn(self.width, self.height, |x, y| {
+      let value = self.values[(y * self.width + x) as usize];
+      Luma([if is_edge(x, y, value) { 255 } else { 0 }])
+    })
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  /// An 8 x 8 image, black left of column `edge` and `level` gray from it on.
+  fn step(edge: u32, level: impl Fn(u32) -> u8) -> DynamicImage {
+    DynamicImage::ImageLuma8(GrayImage::from_fn(8, 8, |x, y| {
+      Luma([if x < edge { 0 } else { level(y) }])
+    }))
+  }
+
+  fn canny_columns(img: &DynamicImage, low: f64, high: f64) -> Vec<Vec<u32>> {
+    let params = EdgeParams {
+      operator: Operator::Canny,
+      sigma: 0.0,
+      low,
+      high,
+      ..EdgeParams::default()
+    };
+    let edges = detect(img, params).to_luma8();
+    (0..8)
+      .map(|y| {
+        (0..8)
+          .filter(|&x| edges.get_pixel(x, y)[0] == 255)
+          .collect()
+      })
+      .collect()
+  }
+
+  #[test]
+  fn a_step_has_a_full_strength_gradient_pointing_across_it() {
+    let plane = Plane::luminance(&step(4, |_| 255));
+    let light = plane.get(7, 5);
+    let (magnitude, direction) = gradients(&plane, true);
+    let (prewitt, _) = gradients(&plane, false);
+
+    for x in 0..8 {
+      let expected = if x == 3 || x == 4 { light } else { 0.0 };
+      assert!((magnitude.get(x, 5) - expected).abs() < 1e-9, "x = {}", x);
+      assert!((prewitt.get(x, 5) - expected).abs() < 1e-9, "x = {}", x);
+    }
+    // Dark to light runs left to right, so the gradient points along the x axis, and the other
+    // way round for light to dark.
+    assert!(direction.get(3, 5).abs() < 1e-9);
+    let (_, reversed) = gradients(&plane.map(|value| light - value), true);
+    assert!((reversed.get(3, 5).abs() - std::f64::consts::PI).abs() < 1e-9);
+  }
+
+  #[test]
+  fn canny_thins_a_step_to_one_column() {
+    assert_eq!(
+      canny_columns(&step(4, |_| 255), 20.0, 50.0),
+      vec![vec![4]; 8]
+    );
+  }
+
+  #[test]
+  fn canny_keeps_weak_edges_only_when_they_touch_strong_ones() {
+    // The step fades from strong at the top to weak at the bottom, gently enough that the fade
+    // itself isn't an edge.
+    let fading = step(4, |y| [100, 100, 90, 80, 70, 60, 60, 60][y as usize]);
+    let weak = step(4, |_| 60);
+
+    assert_eq!(canny_columns(&fading, 25.0, 90.0), vec![vec![4]; 8]);
+    assert_eq!(canny_columns(&weak, 25.0, 90.0), vec![Vec::<u32>::new(); 8]);
+    assert_eq!(canny_columns(&weak, 25.0, 50.0), vec![vec![4]; 8]);
+  }
+
+  #[test]
+  fn canny_finds_edges_on_the_border() {
+    assert_eq!(
+      canny_columns(&step(7, |_| 255), 20.0, 50.0),
+      vec![vec![7]; 8]
+    );
+    assert_eq!(
+      canny_columns(&step(1, |_| 255), 20.0, 50.0),
+      vec![vec![1]; 8]
+    );
+  }
+}
@@ -0,0 +1,203 @@
+use image::codecs::png::{CompressionType, FilterType};
+use image::{ColorType, DynamicImage, ImageFormat};
+
+/// How an image is encoded when it's saved. The defaults match what the `image` crate picks on
+/// its own.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SaveOptions {
+  /// The format to encode as. `None` picks it from the output's extension.
+  pub format: Option<ImageFormat>,
+  /// JPEG quality, from 1 (smallest) to 100 (best).
+  pub jpeg_quality: u8,
+  pub png_compression: CompressionType,
+  pub png_filter: FilterType,
+  /// The color type to convert to before encoding. `None` keeps the image's own.
+  pub color: Option<ColorType>,
+}
+
+impl Default for SaveOptions {
+  fn default() -> Self {
+    SaveOptions {
+      format: None,
+      jpeg_quality: 75,
+      png_compression: CompressionType::Fast,
+      png_filter: FilterType::Sub,
+      color: None,
+    }
+  }
+}
+
+/// Parses an output format by name: `auto`, or any extension of a format that can be written,
+/// such as `png`, `jpg` or `tiff`.
+///
+/// Arguments:
+///
+/// * `name` - &str
+///
+/// Usage: ```encode::parse_format("jpg")?;```
+pub fn parse_format(name: &str) -> std::result::Result<Option<ImageFormat>, String> {
+  if name == "auto" {
+    return Ok(None);
+  }
+
+  match ImageFormat::from_extension(name) {
+    Some(format) if format.can_write() => Ok(Some(format)),
+    _ => Err(
+      "must be auto or a format that can be written, like png, jpg, gif, bmp, ico, tiff or tga"
+        .to_string(),
+    ),
+  }
+}
+
+/// The PNG compression names `parse_compression` accepts.
+pub const COMPRESSION_NAMES: [&str; 5] = ["fast", "default", "best", "huffman", "rle"];
+
+/// Parses a PNG compression level by name.
+///
+/// Arguments:
+///
+/// * `name` - &str
+///
+/// Usage: ```encode::parse_compression("best")?;```
+pub fn parse_compression(name: &str) -> std::result::Result<CompressionType, String> {
+  match name {
+    "fast" => Ok(CompressionType::Fast),
+    "default" => Ok(CompressionType::Default),
+    "best" => Ok(CompressionType::Best),
+    "huffman" => Ok(CompressionType::Huffman),
+    "rle" => Ok(CompressionType::Rle),
+    _ => Err(format!("must be one of {}", COMPRESSION_NAMES.join(", "))),
+  }
+}
+
+/// The PNG filter names `parse_png_filter` accepts.
+pub const PNG_FILTER_NAMES: [&str; 5] = ["none", "sub", "up", "avg", "paeth"];
+
+/// Parses a PNG scanline filter by name.
+///
+/// Arguments:
+///
+/// * `name` - &str
+///
+/// Usage: ```encode::parse_png_filter("paeth")?;```
+pub fn parse_png_filter(name: &str) -> std::result::Result<FilterType, String> {
+  match name {
+    "none" => Ok(FilterType::NoFilter),
+    "sub" => Ok(FilterType::Sub),
+    "up" => Ok(FilterType::Up),
+    "avg" => Ok(FilterType::Avg),
+    "paeth" => Ok(FilterType::Paeth),
+    _ => Err(format!("must be one of {}", PNG_FILTER_NAMES.join(", "))),
+  }
+}
+
+/// The color type names `parse_color_type` accepts, besides `auto`.
+pub const COLOR_TYPE_NAMES: [&str; 8] = [
+  "l8", "la8", "rgb8", "rgba8", "l16", "la16", "rgb16", "rgba16",
+];
+
+/// Parses a color type to convert to by name, or `auto` to keep the image's own.
+///
+/// Arguments:
+///
+/// * `name` - &str
+///
+/// Usage: ```encode::parse_color_type("rgba8")?;```
+pub fn parse_color_type(name: &str) -> std::result::Result<Option<ColorType>, String> {
+  match name {
+    "auto" => Ok(None),
+    "l8" => Ok(Some(ColorType::L8)),
+    "la8" => Ok(Some(ColorType::La8)),
+    "rgb8" => Ok(Some(ColorType::Rgb8)),
+    "rgba8" => Ok(Some(ColorType::Rgba8)),
+    "l16" => Ok(Some(ColorType::L16)),
+    "la16" => Ok(Some(ColorType::La16)),
+    "rgb16" => Ok(Some(ColorType::Rgb16)),
+    "rgba16" => Ok(Some(ColorType::Rgba16)),
+    _ => Err(format!(
+      "must be auto or one of {}",
+      COLOR_TYPE_NAMES.join(", ")
+    )),
+  }
+}
+
+/// Converts an image to a color type. Types `parse_color_type` doesn't produce leave the image
+/// unchanged.
+///
+/// Arguments:
+///
+/// * `img` - DynamicImage
+/// * `color` - ColorType
+///
+/// Usage: ```encode::convert(img, ColorType::L8);```
+pub fn convert(img: DynamicImage, color: ColorType) -> DynamicImage {
+  if img.color() == color {
+    return img;
+  }
+
+  match color {
+    ColorType::L8 => DynamicImage::ImageLuma8(img.to_luma8()),
+    ColorType::La8 => DynamicImage::ImageLumaA8(img.to_luma_alpha8()),
+    ColorType::Rgb8 => DynamicImage::ImageRgb8(img.to_rgb8()),
+    ColorType::Rgba8 => DynamicImage::ImageRgba8(img.to_rgba8()),
+    ColorType::L16 => DynamicImage::ImageLuma16(img.to_luma16()),
+    ColorType::La16 => DynamicImage::ImageLumaA16(img.to_luma_alpha16()),
+    ColorType::Rgb16 => DynamicImage::ImageRgb16(img.to_rgb16()),
+    ColorType::Rgba16 => DynamicImage::ImageRgba16(img.to_rgba16()),
+    _ => img,
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use image::GenericImageView;
+
+  #[test]
+  fn formats_parse_by_extension_or_auto() {
+    assert_eq!(parse_format("auto"), Ok(None));
+    assert_eq!(parse_format("png"), Ok(Some(ImageFormat::Png)));
+    assert_eq!(parse_format("jpg"), Ok(Some(ImageFormat::Jpeg)));
+    assert_eq!(parse_format("jpeg"), Ok(Some(ImageFormat::Jpeg)));
+    assert!(parse_format("webp").is_err());
+    assert!(parse_format("txt").is_err());
+  }
+
+  #[test]
+  fn every_listed_name_parses() {
+    for name in COMPRESSION_NAMES.iter() {
+      assert!(parse_compression(name).is_ok(), "{}", name);
+    }
+    for name in PNG_FILTER_NAMES.iter() {
+      assert!(parse_png_filter(name).is_ok(), "{}", name);
+    }
+    for name in COLOR_TYPE_NAMES.iter() {
+      assert!(parse_color_type(name).unwrap().is_some(), "{}", name);
+    }
+  }
+
+  #[test]
+  fn names_map_to_their_settings() {
+    assert_eq!(parse_compression("best"), Ok(CompressionType::Best));
+    assert_eq!(parse_png_filter("none"), Ok(FilterType::NoFilter));
+    assert_eq!(parse_color_type("auto"), Ok(None));
+    assert_eq!(parse_color_type("la16"), Ok(Some(ColorType::La16)));
+  }
+
+  #[test]
+  fn conversion_changes_only_the_color_type() {
+    let img = DynamicImage::new_rgb8(3, 2);
+
+    let converted = convert(img.clone(), ColorType::La16);
+    assert_eq!(converted.color(), ColorType::La16);
+    assert_eq!(converted.dimensions(), (3, 2));
+    assert_eq!(convert(img.clone(), ColorType::Rgb8), img);
+  }
+
+  #[test]
+  fn unknown_names_are_rejected() {
+    assert!(parse_compression("fastest").is_err());
+    assert!(parse_png_filter("average").is_err());
+    assert!(parse_color_type("rgb32f").is_err());
+  }
+}
@@ -0,0 +1,125 @@
+use image::ImageError;
+use std::error::Error;
+use std::fmt;
+use std::io;
+
+/// Everything that can go wrong while parsing arguments for, opening, manipulating or saving an
+/// image.
+#[derive(Debug)]
+pub enum ImageUtilsError {
+  /// A file couldn't be read or written.
+  Io { path: String, source: io::Error },
+  /// A file was read, but couldn't be decoded as an image.
+  Decode { path: String, source: ImageError },
+  /// An image couldn't be encoded to the requested output.
+  Encode { path: String, source: ImageError },
+  /// A subcommand received the wrong number of arguments.
+  ArgumentCount {
+    subcommand: String,
+    expected: usize,
+    received: usize,
+  },
+  /// A subcommand argument couldn't be parsed or is out of range.
+  BadArgument {
+    subcommand: String,
+    argument: String,
+    reason: String,
+  },
+  /// A crop rectangle doesn't fit inside the image.
+  CropOutOfBounds {
+    x: u32,
+    y: u32,
+    width: u32,
+    height: u32,
+    image_width: u32,
+    image_height: u32,
+  },
+  /// A subcommand or pipeline stage doesn't exist.
+  UnknownSubcommand(String),
+  /// Some of the files in a batch couldn't be processed.
+  BatchFailed { failed: usize, total: usize },
+}
+
+/// Shorthand for results returned by `image_utils`.
+pub type Result<T> = std::result::Result<T, ImageUtilsError>;
+
+impl ImageUtilsError {
+  /// Creates a `BadArgument` error for a subcommand's `argument`.
+  ///
+  /// Arguments:
+  ///
+  /// * `subcommand` - &str
+  /// * `argument` - &str
+  /// * `reason` - impl Into<String>
+  ///
+  /// Usage: ```ImageUtilsError::bad_argument("crop", "x", "is an invalid number");```
+  pub fn bad_argument(subcommand: &str, argument: &str, reason: impl Into<String>) -> Self {
+    ImageUtilsError::BadArgument {
+      subcommand: subcommand.to_string(),
+      argument: argument.to_string(),
+      reason: reason.into(),
+    }
+  }
+}
+
+impl fmt::Display for ImageUtilsError {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      ImageUtilsError::Io { path, source } => write!(f, "Unable to access {}: {}", path, source),
+      ImageUtilsError::Decode { path, source } => {
+        write!(f, "Unable to decode {}: {}", path, source)
+      }
+      ImageUtilsError::Encode { path, source } => {
+        write!(f, "Unable to encode {}: {}", path, source)
+      }
+      ImageUtilsError::ArgumentCount {
+        subcommand,
+        expected,
+        received,
+      } => write!(
+        f,
+        "Invalid {} arguments. Expected {} argument(s), but received {}.",
+        subcommand, expected, received
+      ),
+      ImageUtilsError::BadArgument {
+        subcommand,
+        argument,
+        reason,
+      } => write!(
+        f,
+        "The <{}> argument passed to {} {}.",
+        argument, subcommand, reason
+      ),
+      ImageUtilsError::CropOutOfBounds {
+        x,
+        y,
+        width,
+        height,
+        image_width,
+        image_height,
+      } => write!(
+        f,
+        "A {}x{} crop at ({}, {}) doesn't fit inside a {}x{} image.",
+        width, height, x, y, image_width, image_height
+      ),
+      ImageUtilsError::UnknownSubcommand(subcommand) => {
+        write!(f, "Unknown subcommand or stage {}.", subcommand)
+      }
+      ImageUtilsError::BatchFailed { failed, total } => {
+        write!(f, "{} of {} file(s) failed.", failed, total)
+      }
+    }
+  }
+}
+
+impl Error for ImageUtilsError {
+  fn source(&self) -> Option<&(dyn Error + 'static)> {
+    match self {
+      ImageUtilsError::Io { source, .. } => Some(source),
+      ImageUtilsError::Decode { source, .. } | ImageUtilsError::Encode { source, .. } => {
+        Some(source)
+      }
+      _ => None,
+    }
+  }
+}
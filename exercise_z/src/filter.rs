@@ -0,0 +1,402 @@
+use crate::transform::{self, is_16_bit, with_color_type, Channel};
+use image::{DynamicImage, ImageBuffer, Rgba};
+use std::fmt;
+use std::str::FromStr;
+
+/// A grid of weights to convolve an image with. Both sides have an odd length so the kernel has
+/// a middle.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Kernel {
+  width: usize,
+  height: usize,
+  /// Row by row, top to bottom.
+  weights: Vec<f64>,
+}
+
+impl Kernel {
+  /// Creates a kernel from its rows of weights.
+  ///
+  /// Arguments:
+  ///
+  /// * `rows` - Vec<Vec<f64>>
+  ///
+  /// Usage: ```Kernel::new(vec![vec![0.0, -1.0, 0.0], vec![-1.0, 5.0, -1.0], vec![0.0, -1.0, 0.0]])?;```
+  pub fn new(rows: Vec<Vec<f64>>) -> std::result::Result<Self, String> {
+    let height = rows.len();
+    let width = rows.first().map_or(0, |row| row.len());
+    if rows.iter().any(|row| row.len() != width) {
+      return Err("has rows of different lengths".to_string());
+    }
+    if width % 2 != 1 || height % 2 != 1 {
+      return Err("must have an odd number of rows and columns".to_string());
+    }
+    if rows.iter().flatten().any(|weight| !weight.is_finite()) {
+      return Err("has a weight that isn't a finite number".to_string());
+    }
+
+    Ok(Kernel {
+      width,
+      height,
+      weights: rows.into_iter().flatten().collect(),
+    })
+  }
+
+  /// Scales the weights so they add up to 1, keeping the brightness of the image. Kernels whose
+  /// weights add up to 0, like edge detectors, are returned unchanged.
+  pub fn normalized(&self) -> Kernel {
+    let sum: f64 = self.weights.iter().sum();
+    if sum.abs() < f64::EPSILON {
+      return self.clone();
+    }
+
+    Kernel {
+      weights: self.weights.iter().map(|weight| weight / sum).collect(),
+      ..self.clone()
+    }
+  }
+}
+
+impl FromStr for Kernel {
+  type Err = String;
+
+  /// Parses rows separated by `;` or new lines, with weights separated by `,` or spaces, such as
+  /// `1,2,1;2,4,2;1,2,1`. Anything after a `#` on a line is a comment.
+  fn from_str(spec: &str) -> std::result::Result<Self, Self::Err> {
+    let mut rows = Vec::new();
+    for line in spec.lines() {
+      let line = line.split('#').next().unwrap_or_default();
+      for row in line.split(';').filter(|row| !row.trim().is_empty()) {
+        let weights = row
+          .split(|c: char| c == ',' || c.is_whitespace())
+          .filter(|weight| !weight.is_empty())
+          .map(|weight| weight.parse::<f64>())
+          .collect::<std::result::Result<Vec<f64>, _>>()
+          .map_err(|_| "must be rows of numbers like 1,2,1;2,4,2;1,2,1".to_string())?;
+        rows.push(weights);
+      }
+    }
+
+    Kernel::new(rows)
+  }
+}
+
+/// How a convolution treats the pixels past the edges of an image.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EdgeMode {
+  /// Repeats the nearest edge pixel.
+  Clamp,
+  /// Continues from the opposite edge, as if the image were tiled.
+  Wrap,
+  /// Reflects the image back from the edge.
+  Mirror,
+  /// Treats everything past the edge as black.
+  Zero,
+}
+
+impl EdgeMode {
+  /// The names `EdgeMode::from_str` accepts.
+  pub const NAMES: [&'static str; 4] = ["clamp", "wrap", "mirror", "zero"];
+
+  /// Maps a `position` that might be past either end of `0..length` back inside it, or `None`
+  /// for zero padding.
+  pub fn resolve(self, position: i64, length: i64) -> Option<i64> {
+    if (0..length).contains(&position) {
+      return Some(position);
+    }
+
+    match self {
+      EdgeMode::Clamp => Some(position.clamp(0, length - 1)),
+      EdgeMode::Wrap => Some(position.rem_euclid(length)),
+      EdgeMode::Mirror => {
+        // Reflect without repeating the edge pixel: -1 becomes 1, length becomes length - 2.
+        let period = (2 * (length - 1)).max(1);
+        let folded = position.rem_euclid(period);
+        Some(if folded < length {
+          folded
+        } else {
+          period - folded
+        })
+      }
+      EdgeMode::Zero => None,
+    }
+  }
+}
+
+impl FromStr for EdgeMode {
+  type Err = String;
+
+  fn from_str(name: &str) -> std::result::Result<Self, Self::Err> {
+    match name {
+      "clamp" => Ok(EdgeMode::Clamp),
+      "wrap" => Ok(EdgeMode::Wrap),
+      "mirror" => Ok(EdgeMode::Mirror),
+      "zero" => Ok(EdgeMode::Zero),
+      _ => Err(format!("must be one of {}", EdgeMode::NAMES.join(", "))),
+    }
+  }
+}
+
+impl fmt::Display for EdgeMode {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      EdgeMode::Clamp => write!(f, "clamp"),
+      EdgeMode::Wrap => write!(f, "wrap"),
+      EdgeMode::Mirror => write!(f, "mirror"),
+      EdgeMode::Zero => write!(f, "zero"),
+    }
+  }
+}
+
+/// Sharpens an image with an unsharp mask: the difference between the image and a gaussian blur
+/// of it, `radius` wide, is scaled by `amount` and added back. Differences smaller than
+/// `threshold` (0 to 255) are left alone so flat areas don't pick up noise.
+///
+/// Arguments:
+///
+/// * `img` - &DynamicImage
+/// * `radius` - f32
+/// * `amount` - f64
+/// * `threshold` - f64
+///
+/// Usage: ```filter::sharpen(&img, 1.5, 0.8, 2.0);```
+pub fn sharpen(img: &DynamicImage, radius: f32, amount: f64, threshold: f64) -> DynamicImage {
+  let blurred = transform::blur(img, radius);
+
+  let sharpened = if is_16_bit(img) {
+    DynamicImage::ImageRgba16(unsharp_mask(
+      &img.to_rgba16(),
+      &blurred.to_rgba16(),
+      amount,
+      threshold,
+    ))
+  } else {
+    DynamicImage::ImageRgba8(unsharp_mask(
+      &img.to_rgba8(),
+      &blurred.to_rgba8(),
+      amount,
+      threshold,
+    ))
+  };
+
+  with_color_type(sharpened, img)
+}
+
+fn unsharp_mask<S: Channel>(
+  src: &ImageBuffer<Rgba<S>, Vec<S>>,
+  blurred: &ImageBuffer<Rgba<S>, Vec<S>>,
+  amount: f64,
+  threshold: f64,
+) -> ImageBuffer<Rgba<S>, Vec<S>> {
+  let threshold = threshold / 255.0 * S::MAX_VALUE;
+
+  let mut out = src.clone();
+  for (pixel, soft) in out.pixels_mut().zip(blurred.pixels()) {
+    // Leave alpha alone.
+    for (channel, soft) in pixel.0.iter_mut().zip(soft.0.iter()).take(3) {
+      let value: f64 = (*channel).into();
+      let difference = value - (*soft).into();
+      if difference.abs() >= threshold {
+        *channel = S::from_f64(value + difference * amount);
+      }
+    }
+  }
+
+  out
+}
+
+/// Convolves the color channels of an image with a `kernel`, adding `bias` (0 to 255) to every
+/// result. Alpha is left alone. This is a true convolution: the kernel is flipped both ways before
+/// it's slid over the image, so the bottom-right weight lands on the top-left neighbor.
+///
+/// Arguments:
+///
+/// * `img` - &DynamicImage
+/// * `kernel` - &Kernel
+/// * `bias` - f64
+/// * `edge` - EdgeMode
+///
+/// Usage: ```filter::convolve(&img, &"0,-1,0;-1,5,-1;0,-1,0".parse()?, 0.0, EdgeMode::Clamp);```
+pub fn convolve(img: &DynamicImage, kernel: &Kernel, bias: f64, edge: EdgeMode) -> DynamicImage {
+  let convolved = if is_16_bit(img) {
+    DynamicImage::ImageRgba16(convolve_rgba(&img.to_rgba16(), kernel, bias, edge))
+  } else {
+    DynamicImage::ImageRgba8(convolve_rgba(&img.to_rgba8(), kernel, bias, edge))
+  };
+
+  with_color_type(convolved, img)
+}
+
+fn convolve_rgba<S: Channel>(
+  src: &ImageBuffer<Rgba<S>, Vec<S>>,
+  kernel: &Kernel,
+  bias: f64,
+  edge: EdgeMode,
+) -> ImageBuffer<Rgba<S>, Vec<S>> {
+  let (width, height) = (i64::from(src.width()), i64::from(src.height()));
+  let (reach_x, reach_y) = ((kernel.width / 2) as i64, (kernel.height / 2) as i64);
+  let bias = bias / 255.0 * S::MAX_VALUE;
+
+  ImageBuffer::from_fn(src.width(), src.height(), |x, y| {
+    let mut sum = [bias; 3];
+    for (row, weights) in kernel.weights.chunks(kernel.width).enumerate() {
+      // Flip the kernel: the last row weighs the row above the pixel.
+      let sy = match edge.resolve(i64::from(y) + reach_y - row as i64, height) {
+        Some(sy) => sy as u32,
+        None => continue,
+      };
+      for (column, weight) in weights.iter().enumerate() {
+        if let Some(sx) = edge.resolve(i64::from(x) + reach_x - column as i64, width) {
+          let pixel = src.get_pixel(sx as u32, sy);
+          for (total, channel) in sum.iter_mut().zip(pixel.0.iter()) {
+            *total += weight * (*channel).into();
+          }
+        }
+      }
+    }
+
+    let mut pixel = *src.get_pixel(x, y);
+    for (channel, total) in pixel.0.iter_mut().zip(sum.iter()) {
+      *channel = S::from_f64(*total);
+    }
+    pixel
+  })
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use image::{ColorType, GrayImage, Luma};
+
+  fn step() -> DynamicImage {
+    DynamicImage::ImageLuma8(GrayImage::from_fn(8, 3, |x, _| {
+      Luma([if x < 4 { 60 } else { 180 }])
+    }))
+  }
+
+  fn row(img: &DynamicImage) -> Vec<u8> {
+    let gray = img.to_luma8();
+    (0..gray.width()).map(|x| gray.get_pixel(x, 1)[0]).collect()
+  }
+
+  #[test]
+  fn sharpening_steepens_edges_and_leaves_flat_areas_alone() {
+    let sharpened = sharpen(&step(), 1.0, 1.0, 0.0);
+    let values = row(&sharpened);
+
+    assert_eq!(sharpened.color(), ColorType::L8);
+    assert!(values[3] < 60 && values[4] > 180, "{:?}", values);
+    assert_eq!((values[0], values[7]), (60, 180));
+  }
+
+  #[test]
+  fn no_amount_or_a_high_threshold_changes_nothing() {
+    assert_eq!(row(&sharpen(&step(), 1.0, 0.0, 0.0)), row(&step()));
+    assert_eq!(row(&sharpen(&step(), 1.0, 2.0, 255.0)), row(&step()));
+  }
+
+  #[test]
+  fn sharpening_keeps_16_bit_images_16_bit() {
+    let deep = DynamicImage::ImageLuma16(step().to_luma16());
+
+    assert_eq!(sharpen(&deep, 1.0, 1.0, 0.0).color(), ColorType::L16);
+  }
+
+  #[test]
+  fn kernels_parse_with_any_separator_and_comments() {
+    let expected = Kernel::new(vec![
+      vec![1.0, 2.0, 1.0],
+      vec![2.0, 4.0, 2.0],
+      vec![1.0, 2.0, 1.0],
+    ])
+    .unwrap();
+
+    assert_eq!("1,2,1;2,4,2;1,2,1".parse::<Kernel>().unwrap(), expected);
+    assert_eq!(
+      "# gaussian\n1 2 1\n2, 4, 2 # middle\n1 2 1\n"
+        .parse::<Kernel>()
+        .unwrap(),
+      expected
+    );
+  }
+
+  #[test]
+  fn kernels_must_be_odd_rectangles_of_finite_numbers() {
+    assert!("1,1;1,1".parse::<Kernel>().is_err());
+    assert!("1,2,1;2,4".parse::<Kernel>().is_err());
+    assert!("1,x,1".parse::<Kernel>().is_err());
+    assert!("inf".parse::<Kernel>().is_err());
+    assert!("".parse::<Kernel>().is_err());
+  }
+
+  #[test]
+  fn normalizing_keeps_zero_sum_kernels() {
+    let edges: Kernel = "-1,-1,-1;-1,8,-1;-1,-1,-1".parse().unwrap();
+    let box_blur: Kernel = "1,1,1;1,1,1;1,1,1".parse().unwrap();
+
+    assert_eq!(edges.normalized(), edges);
+    assert!(box_blur
+      .normalized()
+      .weights
+      .iter()
+      .all(|&weight| (weight - 1.0 / 9.0).abs() < 1e-12));
+  }
+
+  #[test]
+  fn convolving_flips_the_kernel() {
+    let img = DynamicImage::ImageLuma8(ImageBuffer::from_raw(3, 1, vec![10, 20, 30]).unwrap());
+    let shift: Kernel = "1,0,0".parse().unwrap();
+    let convolved = convolve(&img, &shift, 0.0, EdgeMode::Clamp);
+
+    // The weight left of the middle picks up the pixel to the right.
+    assert_eq!(convolved.to_luma8().into_raw(), vec![20, 30, 30]);
+  }
+
+  #[test]
+  fn edge_modes_resolve_positions_past_the_edges() {
+    let resolve = |mode: EdgeMode| -> Vec<Option<i64>> {
+      (-2..7).map(|position| mode.resolve(position, 5)).collect()
+    };
+
+    assert_eq!(
+      resolve(EdgeMode::Clamp),
+      [0, 0, 0, 1, 2, 3, 4, 4, 4]
+        .iter()
+        .map(|&p| Some(p))
+        .collect::<Vec<_>>()
+    );
+    assert_eq!(
+      resolve(EdgeMode::Wrap),
+      [3, 4, 0, 1, 2, 3, 4, 0, 1]
+        .iter()
+        .map(|&p| Some(p))
+        .collect::<Vec<_>>()
+    );
+    assert_eq!(
+      resolve(EdgeMode::Mirror),
+      [2, 1, 0, 1, 2, 3, 4, 3, 2]
+        .iter()
+        .map(|&p| Some(p))
+        .collect::<Vec<_>>()
+    );
+    assert_eq!(
+      resolve(EdgeMode::Zero),
+      vec![
+        None,
+        None,
+        Some(0),
+        Some(1),
+        Some(2),
+        Some(3),
+        Some(4),
+        None,
+        None
+      ]
+    );
+  }
+
+  #[test]
+  fn mirroring_a_single_pixel_stays_on_it() {
+    assert_eq!(EdgeMode::Mirror.resolve(-3, 1), Some(0));
+    assert_eq!(EdgeMode::Mirror.resolve(2, 1), Some(0));
+  }
+}
@@ -0,0 +1,608 @@
+use crate::palette::Palette;
+use crate::render::par_render;
+use image::DynamicImage;
+use num_complex::Complex;
+use num_traits::{AsPrimitive, Float};
+use std::fmt;
+use std::str::FromStr;
+
+/// The smallest pixel, in units of the complex plane, that's still iterated in `f32`.
+const F32_PIXEL_SIZE: f64 = 1e-4;
+
+/// The escape-time fractals that can be rendered. Every family iterates `z` from a starting point
+/// until it escapes or runs out of iterations.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Family {
+  /// `z = z * z + c`, starting at the pixel with a fixed `c`.
+  Julia,
+  /// `z = z * z + c`, starting at 0 with `c` at the pixel.
+  Mandelbrot,
+  /// Like the mandelbrot set, but folds `z` into the first quadrant before squaring it.
+  BurningShip,
+  /// Like the mandelbrot set, but squares the complex conjugate of `z`.
+  Tricorn,
+  /// `z = z^n + c`, starting at 0 with `c` at the pixel.
+  Multibrot(u32),
+}
+
+impl Family {
+  /// The names `Family::from_str` accepts.
+  pub const NAMES: [&'static str; 5] = [
+    "julia",
+    "mandelbrot",
+    "burning-ship",
+    "tricorn",
+    "multibrot",
+  ];
+
+  /// The power `z` is raised to on every iteration.
+  pub fn degree(self) -> f64 {
+    match self {
+      Family::Multibrot(power) => f64::from(power),
+      _ => 2.0,
+    }
+  }
+
+  /// Iterates a `point` until it escapes, returning how many iterations that took (or
+  /// `max_iterations` if it never did) and where `z` ended up. Works in `f32` or `f64`.
+  ///
+  /// Arguments:
+  ///
+  /// * `point` - Complex<T>
+  /// * `constant` - Complex<T> (only used by `Julia`)
+  /// * `max_iterations` - u32
+  /// * `escape_radius` - T
+  ///
+  /// Usage: ```Family::Mandelbrot.escape_time(point, constant, 255, 2.0);```
+  pub fn escape_time<T: Float>(
+    self,
+    point: Complex<T>,
+    constant: Complex<T>,
+    max_iterations: u32,
+    escape_radius: T,
+  ) -> (u32, Complex<T>) {
+    let (mut z, c) = match self {
+      Family::Julia => (point, constant),
+      _ => (Complex::new(T::zero(), T::zero()), point),
+    };
+
+    let mut iterations = 0;
+    while iterations < max_iterations && z.norm() <= escape_radius {
+      z = match self {
+        Family::Julia | Family::Mandelbrot => z * z,
+        Family::BurningShip => {
+          let folded = Complex::new(z.re.abs(), z.im.abs());
+          folded * folded
+        }
+        Family::Tricorn => z.conj() * z.conj(),
+        Family::Multibrot(power) => z.powu(power),
+      } + c;
+      iterations += 1;
+    }
+
+    (iterations, z)
+  }
+}
+
+impl FromStr for Family {
+  type Err = String;
+
+  /// Parses a family by name. `multibrot` defaults to a power of 3; use `Family::Multibrot(n)`
+  /// directly for anything else.
+  fn from_str(name: &str) -> Result<Self, Self::Err> {
+    match name {
+      "julia" => Ok(Family::Julia),
+      "mandelbrot" => Ok(Family::Mandelbrot),
+      "burning-ship" => Ok(Family::BurningShip),
+      "tricorn" => Ok(Family::Tricorn),
+      "multibrot" => Ok(Family::Multibrot(3)),
+      _ => Err(format!("must be one of {}", Family::NAMES.join(", "))),
+    }
+  }
+}
+
+impl fmt::Display for Family {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      Family::Julia => write!(f, "julia"),
+      Family::Mandelbrot => write!(f, "mandelbrot"),
+      Family::BurningShip => write!(f, "burning-ship"),
+      Family::Tricorn => write!(f, "tricorn"),
+      Family::Multibrot(power) => write!(f, "multibrot (z^{} + c)", power),
+    }
+  }
+}
+
+/// The region of the complex plane a fractal is rendered over.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Viewport {
+  /// The point at the middle of the image.
+  pub center: Complex<f64>,
+  /// How far to zoom in. At `1.0` the shorter side of the image spans 3.0 units.
+  pub zoom: f64,
+}
+
+impl Viewport {
+  /// Maps the top-left corner of pixel (`x`, `y`) to a point on the complex plane. As in the
+  /// original renderer, the real axis runs top to bottom and the imaginary axis left to right.
+  ///
+  /// Arguments:
+  ///
+  /// * `x` - u32
+  /// * `y` - u32
+  /// * `width` - u32
+  /// * `height` - u32
+  ///
+  /// Usage: ```viewport.point(400, 300, 800, 600);```
+  pub fn point(&self, x: u32, y: u32, width: u32, height: u32) -> Complex<f64> {
+    self.subpixel_point(f64::from(x), f64::from(y), width, height)
+  }
+
+  /// Maps a position measured in pixels, which doesn't have to land on a pixel corner, to a point
+  /// on the complex plane.
+  ///
+  /// Arguments:
+  ///
+  /// * `x` - f64
+  /// * `y` - f64
+  /// * `width` - u32
+  /// * `height` - u32
+  ///
+  /// Usage: ```viewport.subpixel_point(400.5, 300.5, 800, 600);```
+  pub fn subpixel_point(&self, x: f64, y: f64, width: u32, height: u32) -> Complex<f64> {
+    self.locate(x, y, width, height)
+  }
+
+  /// How far apart neighboring pixels are on the complex plane.
+  ///
+  /// Arguments:
+  ///
+  /// * `width` - u32
+  /// * `height` - u32
+  ///
+  /// Usage: ```viewport.pixel_size(800, 600);```
+  pub fn pixel_size(&self, width: u32, height: u32) -> f64 {
+    3.0 / (self.zoom * f64::from(width.min(height)))
+  }
+
+  /// `subpixel_point` in `f32` or `f64`. The position is scaled and offset from the image's
+  /// top-left corner in the target precision, the way the original renderer did it.
+  fn locate<T: Float + 'static>(&self, x: f64, y: f64, width: u32, height: u32) -> Complex<T>
+  where
+    f64: AsPrimitive<T>,
+  {
+    let scale = self.pixel_size(width, height);
+    let top = self.center.re - f64::from(height) / 2.0 * scale;
+    let left = self.center.im - f64::from(width) / 2.0 * scale;
+
+    Complex::new(
+      y.as_() * scale.as_() + top.as_(),
+      x.as_() * scale.as_() + left.as_(),
+    )
+  }
+
+  /// The viewport `t` of the way from `self` to `end`. The zoom changes geometrically and the
+  /// center moves in step with the shrinking view, so a point that stays on screen drifts
+  /// smoothly towards the end center instead of racing past it.
+  ///
+  /// Arguments:
+  ///
+  /// * `end` - &Viewport
+  /// * `t` - f64 (between 0.0 and 1.0)
+  ///
+  /// Usage: ```start.interpolate(&end, 0.5);```
+  pub fn interpolate(&self, end: &Viewport, t: f64) -> Viewport {
+    let zoom = self.zoom * (end.zoom / self.zoom).powf(t);
+    let travel = if (end.zoom - self.zoom).abs() > f64::EPSILON {
+      (1.0 / self.zoom - 1.0 / zoom) / (1.0 / self.zoom - 1.0 / end.zoom)
+    } else {
+      t
+    };
+
+    Viewport {
+      center: self.center + (end.center - self.center) * travel,
+      zoom,
+    }
+  }
+}
+
+/// How escape times are turned into colors.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Coloring {
+  /// The iteration count in the green channel over a red/blue gradient.
+  Classic,
+  /// The iteration count mapped through a palette, with points inside the set left black.
+  /// `smooth` uses the normalized iteration count, which removes the banding between iterations.
+  Palette { palette: Palette, smooth: bool },
+}
+
+/// Everything that determines what a fractal looks like.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FractalParams {
+  pub family: Family,
+  pub width: u32,
+  pub height: u32,
+  /// The `c` in `z = z * z + c` for julia sets.
+  pub constant: Complex<f64>,
+  pub viewport: Viewport,
+  /// How many times to iterate before deciding a point is inside the set.
+  pub max_iterations: u32,
+  /// How far `z` has to get from the origin before a point has escaped. Smooth coloring takes the
+  /// log of the log of where `z` ends up, so this must be greater than 1.
+  pub escape_radius: f64,
+  pub coloring: Coloring,
+  /// Renders every pixel as the average of a `samples` x `samples` grid to smooth jagged edges.
+  /// `1` takes a single sample at the pixel's corner.
+  pub samples: u32,
+}
+
+impl Default for FractalParams {
+  fn default() -> Self {
+    FractalParams {
+      family: Family::Julia,
+      width: 800,
+      height: 800,
+      constant: Complex::new(-0.4, 0.6),
+      viewport: Viewport {
+        center: Complex::new(0.0, 0.0),
+        zoom: 1.0,
+      },
+      max_iterations: 255,
+      escape_radius: 2.0,
+      coloring: Coloring::Classic,
+      samples: 1,
+    }
+  }
+}
+
+/// Renders an escape-time fractal, spreading rows over `threads` workers. The output is
+/// byte-identical for any thread count.
+///
+/// Arguments:
+///
+/// * `params` - &FractalParams
+/// * `threads` - usize (0 uses every core)
+///
+/// Usage: ```fractal::render(&FractalParams::default(), 0);```
+pub fn render(params: &FractalParams, threads: usize) -> DynamicImage {
+  let (width, height) = (params.width, params.height);
+
+  DynamicImage::ImageRgb8(par_render(width, height, threads, |x, y| {
+    if params.samples <= 1 {
+      return sample(params, f64::from(x), f64::from(y), x, y);
+    }
+
+    let samples = params.samples;
+    let step = 1.0 / f64::from(samples);
+    let mut sum = [0u32; 3];
+    for sub_y in 0..samples {
+      for sub_x in 0..samples {
+        // Sample the middle of each cell of the grid laid over the pixel.
+        let rgb = sample(
+          params,
+          f64::from(x) + (f64::from(sub_x) + 0.5) * step,
+          f64::from(y) + (f64::from(sub_y) + 0.5) * step,
+          x,
+          y,
+        );
+        for (total, channel) in sum.iter_mut().zip(rgb.iter()) {
+          *total += u32::from(*channel);
+        }
+      }
+    }
+
+    let count = samples * samples;
+    let mut rgb = [0; 3];
+    for (channel, total) in rgb.iter_mut().zip(sum.iter()) {
+      *channel = ((total + count / 2) / count) as u8;
+    }
+
+    rgb
+  }))
+}
+
+/// Iterates the point at (`px`, `py`) pixels, inside the pixel at (`x`, `y`), and colors it.
+/// Views where pixels are at least `F32_PIXEL_SIZE` across are iterated in `f32`, like the
+/// original renderer, so their output doesn't change; deeper zooms need `f64` to tell neighboring
+/// pixels apart.
+fn sample(params: &FractalParams, px: f64, py: f64, x: u32, y: u32) -> [u8; 3] {
+  let (iterations, z) = if params.viewport.pixel_size(params.width, params.height) >= F32_PIXEL_SIZE
+  {
+    iterate::<f32>(params, px, py)
+  } else {
+    iterate::<f64>(params, px, py)
+  };
+
+  shade(params, x, y, iterations, z)
+}
+
+/// Iterates the point at (`px`, `py`) pixels in `T`.
+fn iterate<T: Float + Into<f64> + 'static>(
+  params: &FractalParams,
+  px: f64,
+  py: f64,
+) -> (u32, Complex<f64>)
+where
+  f64: AsPrimitive<T>,
+{
+  let point = params
+    .viewport
+    .locate::<T>(px, py, params.width, params.height);
+  let constant = Complex::new(params.constant.re.as_(), params.constant.im.as_());
+  let (iterations, z) = params.family.escape_time(
+    point,
+    constant,
+    params.max_iterations,
+    params.escape_radius.as_(),
+  );
+
+  (iterations, Complex::new(z.re.into(), z.im.into()))
+}
+
+/// Renders `frames` images zooming from `params.viewport` to `end`, one after another with every
+/// frame spread over `threads` workers. Frames are rendered lazily as the iterator is advanced, so
+/// only one is held in memory at a time.
+///
+/// Arguments:
+///
+/// * `params` - &FractalParams
+/// * `end` - &Viewport
+/// * `frames` - u32
+/// * `threads` - usize (0 uses every core)
+///
+/// Usage: ```fractal::render_zoom(&params, &end, 30, 0);```
+pub fn render_zoom<'a>(
+  params: &'a FractalParams,
+  end: &Viewport,
+  frames: u32,
+  threads: usize,
+) -> impl Iterator<Item = DynamicImage> + 'a {
+  let end = *end;
+  let last = f64::from(frames.saturating_sub(1).max(1));
+
+  (0..frames).map(move |frame| {
+    let viewport = params.viewport.interpolate(&end, f64::from(frame) / last);
+
+    render(
+      &FractalParams {
+        viewport,
+        ..params.clone()
+      },
+      threads,
+    )
+  })
+}
+
+/// Colors the pixel at (`x`, `y`) whose point escaped after `iterations`, ending up at `z`.
+fn shade(params: &FractalParams, x: u32, y: u32, iterations: u32, z: Complex<f64>) -> [u8; 3] {
+  let max_iterations = f64::from(params.max_iterations);
+
+  match &params.coloring {
+    Coloring::Classic => {
+      // Use red and blue to be a pretty gradient background
+      let red = (240.0 * f64::from(x) / f64::from(params.width)) as u8;
+      let blue = (240.0 * f64::from(y) / f64::from(params.height)) as u8;
+      // Use green as the fractal foreground
+      let green = (255.0 * f64::from(iterations) / max_iterations) as u8;
+
+      [red, green, blue]
+    }
+    Coloring::Palette { .. } if iterations >= params.max_iterations => [0, 0, 0],
+    Coloring::Palette { palette, smooth } => {
+      let escape = if *smooth {
+        // Subtract how far past the escape radius `z` overshot, in fractions of an iteration.
+        let overshoot = z.norm().ln().ln() / params.family.degree().ln();
+        f64::from(iterations) + 1.0 - overshoot
+      } else {
+        f64::from(iterations)
+      };
+
+      palette.sample(escape / max_iterations)
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use std::collections::HashSet;
+
+  fn small() -> FractalParams {
+    FractalParams {
+      width: 40,
+      height: 30,
+      ..FractalParams::default()
+    }
+  }
+
+  #[test]
+  fn renders_are_identical_for_any_thread_count() {
+    let smooth = FractalParams {
+      coloring: Coloring::Palette {
+        palette: Palette::builtin("fire").unwrap(),
+        smooth: true,
+      },
+      ..small()
+    };
+    for params in [small(), smooth].iter() {
+      let single = render(params, 1);
+      for &threads in [0, 2, 3, 7].iter() {
+        assert_eq!(render(params, threads).as_bytes(), single.as_bytes());
+      }
+    }
+  }
+
+  #[test]
+  fn families_iterate_their_own_formulas() {
+    let escape = |family: Family, c: Complex<f64>| family.escape_time(c, c, 50, 2.0).0;
+    let (one, i) = (Complex::new(1.0, 0.0), Complex::new(0.0, 1.0));
+
+    // 0, 1, 2, 5 for the mandelbrot set; 0, 1, 2, 9 for the cubic multibrot.
+    assert_eq!(escape(Family::Mandelbrot, one), 3);
+    assert_eq!(escape(Family::Multibrot(3), one), 3);
+    // c = i cycles forever in the mandelbrot set but escapes the tricorn.
+    assert_eq!(escape(Family::Mandelbrot, i), 50);
+    assert_eq!(escape(Family::Tricorn, i), 3);
+    for name in Family::NAMES.iter() {
+      assert!(name.parse::<Family>().is_ok(), "{}", name);
+    }
+    assert!("julius".parse::<Family>().is_err());
+  }
+
+  #[test]
+  fn the_real_axis_runs_down_and_the_imaginary_axis_across() {
+    let viewport = FractalParams::default().viewport;
+
+    assert_eq!(viewport.point(0, 0, 800, 800), Complex::new(-1.5, -1.5));
+    assert_eq!(viewport.point(400, 0, 800, 800), Complex::new(-1.5, 0.0));
+    assert_eq!(viewport.point(0, 400, 800, 800), Complex::new(0.0, -1.5));
+  }
+
+  #[test]
+  fn deep_zooms_are_iterated_in_f64() {
+    let mut params = small();
+    let iterated_in = |params: &FractalParams, x: u32, single_precision: bool| {
+      let (px, py) = (f64::from(x), 10.0);
+      let (iterations, z) = if single_precision {
+        iterate::<f32>(params, px, py)
+      } else {
+        iterate::<f64>(params, px, py)
+      };
+      shade(params, x, 10, iterations, z)
+    };
+    for x in 0..params.width {
+      assert_eq!(
+        sample(&params, f64::from(x), 10.0, x, 10),
+        iterated_in(&params, x, true)
+      );
+    }
+
+    // At this zoom neighboring pixels are closer together than f32 can tell apart.
+    params.viewport = Viewport {
+      center: Complex::new(0.3, 0.4),
+      zoom: 1e7,
+    };
+    assert_eq!(
+      params.viewport.locate::<f32>(0.0, 10.0, 40, 30),
+      params.viewport.locate::<f32>(1.0, 10.0, 40, 30)
+    );
+    assert_ne!(
+      params.viewport.locate::<f64>(0.0, 10.0, 40, 30),
+      params.viewport.locate::<f64>(1.0, 10.0, 40, 30)
+    );
+    for x in 0..params.width {
+      assert_eq!(
+        sample(&params, f64::from(x), 10.0, x, 10),
+        iterated_in(&params, x, false)
+      );
+    }
+  }
+
+  #[test]
+  fn smooth_coloring_blends_between_bands_and_leaves_the_set_black() {
+    let banded = |smooth: bool| FractalParams {
+      family: Family::Mandelbrot,
+      coloring: Coloring::Palette {
+        palette: Palette::builtin("grayscale").unwrap(),
+        smooth,
+      },
+      max_iterations: 20,
+      ..small()
+    };
+    let colors = |params: &FractalParams| -> HashSet<Vec<u8>> {
+      render(params, 1)
+        .as_bytes()
+        .chunks(3)
+        .map(|rgb| rgb.to_vec())
+        .collect()
+    };
+
+    assert!(colors(&banded(true)).len() > colors(&banded(false)).len());
+    // The middle of the view is the origin, which never escapes the mandelbrot set.
+    assert_eq!(sample(&banded(true), 20.0, 15.0, 20, 15), [0, 0, 0]);
+  }
+
+  #[test]
+  fn zooms_scale_geometrically_and_arrive_at_the_end_viewport() {
+    let start = Viewport {
+      center: Complex::new(0.0, 0.0),
+      zoom: 1.0,
+    };
+    let end = Viewport {
+      center: Complex::new(-0.5, 1.0),
+      zoom: 100.0,
+    };
+
+    assert_eq!(start.interpolate(&end, 0.0), start);
+    let last = start.interpolate(&end, 1.0);
+    assert!((last.zoom - end.zoom).abs() < 1e-9);
+    assert!((last.center - end.center).norm() < 1e-9);
+    let middle = start.interpolate(&end, 0.5);
+    assert!((middle.zoom - 10.0).abs() < 1e-9);
+    // Most of the travel happens while the view is still wide.
+    assert!(middle.center.norm() > 0.5 * end.center.norm());
+    // Without a zoom change the center moves linearly.
+    let pan = start.interpolate(&Viewport { zoom: 1.0, ..end }, 0.25);
+    assert_eq!(pan.center, Complex::new(-0.125, 0.25));
+  }
+
+  #[test]
+  fn zoom_animations_start_at_the_start_and_end_at_the_end() {
+    let end = Viewport {
+      center: Complex::new(0.3, 0.1),
+      zoom: 8.0,
+    };
+    let frames: Vec<DynamicImage> = render_zoom(&small(), &end, 3, 2).collect();
+    let at = |viewport: Viewport| {
+      render(
+        &FractalParams {
+          viewport,
+          ..small()
+        },
+        1,
+      )
+    };
+
+    assert_eq!(frames.len(), 3);
+    assert_eq!(frames[0].as_bytes(), render(&small(), 1).as_bytes());
+    assert_eq!(
+      frames[1].as_bytes(),
+      at(small().viewport.interpolate(&end, 0.5)).as_bytes()
+    );
+    assert_eq!(
+      frames[2].as_bytes(),
+      at(small().viewport.interpolate(&end, 1.0)).as_bytes()
+    );
+    assert_eq!(render_zoom(&small(), &end, 1, 1).count(), 1);
+  }
+
+  #[test]
+  fn supersampled_pixels_average_a_grid_of_samples() {
+    let params = FractalParams {
+      samples: 2,
+      ..small()
+    };
+    let supersampled = render(&params, 3).to_rgb8();
+
+    for &(x, y) in [(0, 0), (13, 7), (20, 15), (39, 29)].iter() {
+      let mut sum = [0u32; 3];
+      for &(dx, dy) in [(0.25, 0.25), (0.75, 0.25), (0.25, 0.75), (0.75, 0.75)].iter() {
+        let rgb = sample(&params, f64::from(x) + dx, f64::from(y) + dy, x, y);
+        for (total, channel) in sum.iter_mut().zip(rgb.iter()) {
+          *total += u32::from(*channel);
+        }
+      }
+      let average = [
+        ((sum[0] + 2) / 4) as u8,
+        ((sum[1] + 2) / 4) as u8,
+        ((sum[2] + 2) / 4) as u8,
+      ];
+      assert_eq!(supersampled.get_pixel(x, y).0, average, "({}, {})", x, y);
+    }
+    assert_ne!(supersampled.as_raw(), render(&small(), 1).as_bytes());
+    assert_eq!(
+      render(&params, 1).as_bytes(),
+      supersampled.as_raw().as_slice()
+    );
+  }
+}
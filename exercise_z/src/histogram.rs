@@ -0,0 +1,430 @@
+use crate::adjust::luminance;
+use crate::transform::{is_16_bit, Channel};
+use image::{DynamicImage, ImageBuffer, Rgb, Rgba};
+use std::fmt;
+use std::str::FromStr;
+
+/// The width of a rendered chart in pixels.
+const CHART_WIDTH: u32 = 512;
+/// The height of each panel of a rendered chart in pixels.
+const PANEL_HEIGHT: u32 = 160;
+const CHART_BACKGROUND: [u8; 3] = [24, 24, 24];
+
+/// How many pixels of a channel hold every value it can take: 256 values for 8-bit images and
+/// 65536 for 16-bit ones. Statistics are reported on a 0 to 255 scale either way.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Histogram {
+  /// The channel the counts are for: red, green, blue, alpha or luminance.
+  pub channel: &'static str,
+  counts: Vec<u64>,
+}
+
+impl Histogram {
+  /// The number of pixels counted.
+  pub fn pixels(&self) -> u64 {
+    self.counts.iter().sum()
+  }
+
+  pub fn mean(&self) -> f64 {
+    let pixels = self.pixels();
+    if pixels == 0 {
+      return 0.0;
+    }
+    let sum: f64 = self
+      .counts
+      .iter()
+      .enumerate()
+      .map(|(level, &count)| self.value(level) * count as f64)
+      .sum();
+
+    sum / pixels as f64
+  }
+
+  /// The lower median: the smallest value at least half the pixels are at or below.
+  pub fn median(&self) -> f64 {
+    let half = self.pixels().div_ceil(2);
+    let mut seen = 0;
+    for (level, &count) in self.counts.iter().enumerate() {
+      seen += count;
+      if seen >= half && seen > 0 {
+        return self.value(level);
+      }
+    }
+
+    0.0
+  }
+
+  /// The population standard deviation.
+  pub fn std_dev(&self) -> f64 {
+    let pixels = self.pixels();
+    if pixels == 0 {
+      return 0.0;
+    }
+    let mean = self.mean();
+    let sum: f64 = self
+      .counts
+      .iter()
+      .enumerate()
+      .map(|(level, &count)| (self.value(level) - mean).powi(2) * count as f64)
+      .sum();
+
+    (sum / pixels as f64).sqrt()
+  }
+
+  /// The lowest value any pixel has.
+  pub fn min(&self) -> f64 {
+    self
+      .counts
+      .iter()
+      .position(|&count| count > 0)
+      .map_or(0.0, |level| self.value(level))
+  }
+
+  /// The highest value any pixel has.
+  pub fn max(&self) -> f64 {
+    self
+      .counts
+      .iter()
+      .rposition(|&count| count > 0)
+      .map_or(0.0, |level| self.value(level))
+  }
+
+  /// Groups the counts into `bins` evenly sized bins, darkest first.
+  ///
+  /// Arguments:
+  ///
+  /// * `bins` - usize
+  ///
+  /// Usage: ```histogram.bins(64);```
+  pub fn bins(&self, bins: usize) -> Vec<u64> {
+    let bins = bins.max(1);
+    let mut grouped = vec![0; bins];
+    for (level, &count) in self.counts.iter().enumerate() {
+      grouped[level * bins / self.counts.len()] += count;
+    }
+
+    grouped
+  }
+
+  /// A level on the 0 to 255 scale.
+  fn value(&self, level: usize) -> f64 {
+    level as f64 / (self.counts.len() - 1) as f64 * 255.0
+  }
+}
+
+/// Counts the red, green, blue and luminance values of every pixel of an image, plus alpha if it
+/// has an alpha channel.
+///
+/// Arguments:
+///
+/// * `img` - &DynamicImage
+///
+/// Usage: ```histogram::compute(&img);```
+pub fn compute(img: &DynamicImage) -> Vec<Histogram> {
+  let mut histograms = if is_16_bit(img) {
+    count(&img.to_rgba16())
+  } else {
+    count(&img.to_rgba8())
+  };
+  if !img.color().has_alpha() {
+    histograms.retain(|histogram| histogram.channel != "alpha");
+  }
+
+  histograms
+}
+
+fn count<S: Channel>(src: &ImageBuffer<Rgba<S>, Vec<S>>) -> Vec<Histogram> {
+  let levels = S::MAX_VALUE as usize + 1;
+  let mut counts = vec![vec![0; levels]; 5];
+  for pixel in src.pixels() {
+    let [red, green, blue, alpha]: [f64; 4] = pixel.0.map(|channel| channel.into());
+    let luma = luminance([red, green, blue].map(|channel| channel / S::MAX_VALUE));
+    let levels = [red, green, blue, alpha, (luma * S::MAX_VALUE).round()];
+    for (counts, level) in counts.iter_mut().zip(levels.iter()) {
+      counts[*level as usize] += 1;
+    }
+  }
+
+  ["red", "green", "blue", "alpha", "luminance"]
+    .iter()
+    .zip(counts)
+    .map(|(&channel, counts)| Histogram { channel, counts })
+    .collect()
+}
+
+/// The formats histograms can be exported as.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+  /// A header row, then one row per channel with its statistics and bin counts.
+  Csv,
+  /// An object with the image size, the bin count and an object per channel.
+  Json,
+}
+
+impl Format {
+  /// The names `Format::from_str` accepts.
+  pub const NAMES: [&'static str; 2] = ["csv", "json"];
+}
+
+impl FromStr for Format {
+  type Err = String;
+
+  fn from_str(name: &str) -> Result<Self, Self::Err> {
+    match name {
+      "csv" => Ok(Format::Csv),
+      "json" => Ok(Format::Json),
+      _ => Err(format!("must be one of {}", Format::NAMES.join(", "))),
+    }
+  }
+}
+
+impl fmt::Display for Format {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      Format::Csv => write!(f, "csv"),
+      Format::Json => write!(f, "json"),
+    }
+  }
+}
+
+/// Writes the statistics and `bins` bin counts of histograms of a `width` x `height` image in a
+/// `format`.
+///
+/// Arguments:
+///
+/// * `histograms` - &[Histogram]
+/// * `width` - u32
+/// * `height` - u32
+/// * `bins` - usize
+/// * `format` - Format
+///
+/// Usage: ```histogram::export(&histograms, 640, 480, 256, Format::Json);```
+pub fn export(
+  histograms: &[Histogram],
+  width: u32,
+  height: u32,
+  bins: usize,
+  format: Format,
+) -> String {
+  let stats = |histogram: &Histogram| {
+    [
+      ("mean", histogram.mean()),
+      ("median", histogram.median()),
+      ("std_dev", histogram.std_dev()),
+      ("min", histogram.min()),
+      ("max", histogram.max()),
+    ]
+  };
+  let join = |counts: Vec<u64>, separator: &str| {
+    counts
+      .iter()
+      .map(|count| count.to_string())
+      .collect::<Vec<String>>()
+      .join(separator)
+  };
+
+  match format {
+    Format::Csv => {
+      let mut header = vec!["channel".to_string(), "pixels".to_string()];
+      header.extend(
+        stats(&histograms[0])
+          .iter()
+          .map(|(name, _)| name.to_string()),
+      );
+      header.extend((0..bins).map(|bin| format!("bin_{}", bin)));
+      let mut csv = header.join(",") + "\n";
+      for histogram in histograms {
+        let mut row = vec![
+          histogram.channel.to_string(),
+          histogram.pixels().to_string(),
+        ];
+        row.extend(stats(histogram).iter().map(|(_, value)| value.to_string()));
+        row.push(join(histogram.bins(bins), ","));
+        csv += &(row.join(",") + "\n");
+      }
+      csv
+    }
+    Format::Json => {
+      let channels: Vec<String> = histograms
+        .iter()
+        .map(|histogram| {
+          let mut fields = vec![format!("\"pixels\": {}", histogram.pixels())];
+          fields.extend(
+            stats(histogram)
+              .iter()
+              .map(|(name, value)| format!("\"{}\": {}", name, value)),
+          );
+          fields.push(format!(
+            "\"counts\": [{}]",
+            join(histogram.bins(bins), ", ")
+          ));
+          format!(
+            "    \"{}\": {{\n      {}\n    }}",
+            histogram.channel,
+            fields.join(",\n      ")
+          )
+        })
+        .collect();
+      format!(
+        "{{\n  \"width\": {},\n  \"height\": {},\n  \"bins\": {},\n  \"channels\": {{\n{}\n  }}\n}}\n",
+        width,
+        height,
+        bins,
+        channels.join(",\n")
+      )
+    }
+  }
+}
+
+/// Draws histograms as a chart of `bins` bars: the red, green and blue channels blended on top,
+/// then a panel for each other channel. Every panel is scaled to its tallest bar.
+///
+/// Arguments:
+///
+/// * `histograms` - &[Histogram]
+/// * `bins` - usize
+///
+/// Usage: ```histogram::chart(&histograms, 256);```
+pub fn chart(histograms: &[Histogram], bins: usize) -> DynamicImage {
+  let color = |channel: &str| match channel {
+    "red" => Some([255, 64, 64]),
+    "green" => Some([64, 255, 64]),
+    "blue" => Some([64, 64, 255]),
+    _ => None,
+  };
+  let (colors, others): (Vec<&Histogram>, Vec<&Histogram>) = histograms
+    .iter()
+    .partition(|histogram| color(histogram.channel).is_some());
+  let mut panels: Vec<Vec<(Vec<u64>, [u8; 3])>> = vec![colors
+    .iter()
+    .map(|histogram| (histogram.bins(bins), color(histogram.channel).unwrap()))
+    .collect()];
+  panels.extend(
+    others
+      .iter()
+      .map(|histogram| vec![(histogram.bins(bins), [224, 224, 224])]),
+  );
+  let tallest: Vec<u64> = panels
+    .iter()
+    .map(|panel| {
+      panel
+        .iter()
+        .flat_map(|(counts, _)| counts.iter().cloned())
+        .max()
+        .unwrap_or(0)
+        .max(1)
+    })
+    .collect();
+
+  let height = PANEL_HEIGHT * panels.len() as u32;
+  DynamicImage::ImageRgb8(ImageBuffer::from_fn(CHART_WIDTH, height, |x, y| {
+    let panel = (y / PANEL_HEIGHT) as usize;
+    // How high up its panel the pixel is, from 0.0 at the bottom to 1.0 at the top.
+    let rise = f64::from(PANEL_HEIGHT - y % PANEL_HEIGHT) / f64::from(PANEL_HEIGHT);
+    let bin = x as usize * bins / CHART_WIDTH as usize;
+
+    let mut pixel = CHART_BACKGROUND;
+    for (counts, color) in &panels[panel] {
+      if counts[bin] as f64 / tallest[panel] as f64 >= rise {
+        // Blend additively so overlapping channels mix toward white.
+        for (channel, value) in pixel.iter_mut().zip(color.iter()) {
+          *channel = channel.saturating_add(*value);
+        }
+      }
+    }
+    Rgb(pixel)
+  }))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use image::{GrayImage, Luma, RgbImage};
+
+  fn gray(values: &[u8]) -> DynamicImage {
+    DynamicImage::ImageLuma8(GrayImage::from_fn(values.len() as u32, 1, |x, _| {
+      Luma([values[x as usize]])
+    }))
+  }
+
+  fn black_and_white() -> Vec<Histogram> {
+    compute(&DynamicImage::ImageRgb8(RgbImage::from_fn(2, 1, |x, _| {
+      Rgb([if x == 0 { 0 } else { 255 }; 3])
+    })))
+  }
+
+  #[test]
+  fn the_ends_of_the_range_land_in_the_first_and_last_bins() {
+    let histograms = compute(&gray(&[0, 127, 128, 255]));
+    let red = &histograms[0];
+
+    assert_eq!(red.bins(4), vec![1, 1, 1, 1]);
+    assert_eq!(red.bins(3), vec![1, 2, 1]);
+    assert_eq!(red.bins(1), vec![4]);
+    assert_eq!(red.bins(256)[0], 1);
+    assert_eq!(red.bins(256)[255], 1);
+  }
+
+  #[test]
+  fn statistics_describe_the_known_values() {
+    let histograms = compute(&gray(&[10, 20, 30, 250]));
+    let channels: Vec<&str> = histograms
+      .iter()
+      .map(|histogram| histogram.channel)
+      .collect();
+    let red = &histograms[0];
+
+    assert_eq!(channels, ["red", "green", "blue", "luminance"]);
+    assert_eq!(red.pixels(), 4);
+    assert_eq!(red.mean(), 77.5);
+    assert_eq!(red.median(), 20.0);
+    assert_eq!((red.min(), red.max()), (10.0, 250.0));
+    assert!((red.std_dev() - 9968.75f64.sqrt()).abs() < 1e-9);
+    assert_eq!(histograms[3].mean(), 77.5);
+  }
+
+  #[test]
+  fn csv_has_a_row_per_channel_after_the_header() {
+    assert_eq!(
+      export(&black_and_white(), 2, 1, 2, Format::Csv),
+      "channel,pixels,mean,median,std_dev,min,max,bin_0,bin_1\n\
+       red,2,127.5,0,127.5,0,255,1,1\n\
+       green,2,127.5,0,127.5,0,255,1,1\n\
+       blue,2,127.5,0,127.5,0,255,1,1\n\
+       luminance,2,127.5,0,127.5,0,255,1,1\n"
+    );
+  }
+
+  #[test]
+  fn json_nests_an_object_per_channel() {
+    assert_eq!(
+      export(&black_and_white()[..2], 2, 1, 2, Format::Json),
+      r#"{
+  "width": 2,
+  "height": 1,
+  "bins": 2,
+  "channels": {
+    "red": {
+      "pixels": 2,
+      "mean": 127.5,
+      "median": 0,
+      "std_dev": 127.5,
+      "min": 0,
+      "max": 255,
+      "counts": [1, 1]
+    },
+    "green": {
+      "pixels": 2,
+      "mean": 127.5,
+      "median": 0,
+      "std_dev": 127.5,
+      "min": 0,
+      "max": 255,
+      "counts": [1, 1]
+    }
+  }
+}
+"#
+    );
+  }
+}
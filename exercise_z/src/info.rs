@@ -0,0 +1,247 @@
+use crate::histogram;
+use crate::{open_image, ImageUtilsError, Result};
+use image::io::Reader;
+use image::{DynamicImage, GenericImageView};
+use std::collections::HashSet;
+use std::fs;
+
+/// What an image file is and what it holds.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ImageInfo {
+  pub path: String,
+  /// The format guessed from the file's contents, or its extension. `None` if neither is known.
+  pub format: Option<String>,
+  pub width: u32,
+  pub height: u32,
+  /// The color type the image decoded as, such as `Rgba8`.
+  pub color_type: String,
+  /// Bits per channel.
+  pub bit_depth: u16,
+  pub has_alpha: bool,
+  /// The size of the file in bytes.
+  pub file_size: u64,
+  /// How many different colors, including alpha, the pixels have.
+  pub unique_colors: usize,
+  /// The mean of the red, green, blue and, if there is one, alpha channels, on a 0 to 255 scale.
+  pub mean_color: Vec<f64>,
+}
+
+impl ImageInfo {
+  /// Describes the image one property per line.
+  pub fn to_text(&self) -> String {
+    let mean_color: Vec<String> = self
+      .mean_color
+      .iter()
+      .map(|mean| format!("{:.1}", mean))
+      .collect();
+    let lines = [
+      ("file", self.path.clone()),
+      (
+        "format",
+        self.format.clone().unwrap_or_else(|| "unknown".to_string()),
+      ),
+      ("dimensions", format!("{} x {}", self.width, self.height)),
+      ("color type", self.color_type.clone()),
+      ("bit depth", self.bit_depth.to_string()),
+      (
+        "alpha",
+        if self.has_alpha { "yes" } else { "no" }.to_string(),
+      ),
+      ("file size", file_size(self.file_size)),
+      ("unique colors", self.unique_colors.to_string()),
+      ("mean color", mean_color.join(", ")),
+    ];
+
+    lines
+      .iter()
+      .map(|(name, value)| format!("{:<16}{}\n", format!("{}:", name), value))
+      .collect()
+  }
+
+  /// Describes the image as a JSON object.
+  pub fn to_json(&self) -> String {
+    let mean_color: Vec<String> = self
+      .mean_color
+      .iter()
+      .map(|mean| mean.to_string())
+      .collect();
+    let fields = [
+      ("path", json_string(&self.path)),
+      (
+        "format",
+        self
+          .format
+          .as_ref()
+          .map_or_else(|| "null".to_string(), |format| json_string(format)),
+      ),
+      ("width", self.width.to_string()),
+      ("height", self.height.to_string()),
+      ("color_type", json_string(&self.color_type)),
+      ("bit_depth", self.bit_depth.to_string()),
+      ("has_alpha", self.has_alpha.to_string()),
+      ("file_size", self.file_size.to_string()),
+      ("unique_colors", self.unique_colors.to_string()),
+      ("mean_color", format!("[{}]", mean_color.join(", "))),
+    ];
+    let fields: Vec<String> = fields
+      .iter()
+      .map(|(name, value)| format!("  \"{}\": {}", name, value))
+      .collect();
+
+    format!("{{\n{}\n}}\n", fields.join(",\n"))
+  }
+}
+
+/// Opens the image at `path` and inspects it.
+///
+/// Arguments:
+///
+/// * `path` - String
+///
+/// Usage: ```info::inspect("input.png".to_string())?;```
+pub fn inspect(path: String) -> Result<ImageInfo> {
+  let io_error = |source| ImageUtilsError::Io {
+    path: path.clone(),
+    source,
+  };
+  let file_size = fs::metadata(&path).map_err(io_error)?.len();
+  let format = Reader::open(&path)
+    .and_then(|reader| reader.with_guessed_format())
+    .map_err(io_error)?
+    .format()
+    .map(|format| format!("{:?}", format).to_lowercase());
+  let img = open_image(path.clone())?;
+  let color = img.color();
+
+  Ok(ImageInfo {
+    path,
+    format,
+    width: img.width(),
+    height: img.height(),
+    color_type: format!("{:?}", color),
+    bit_depth: color.bits_per_pixel() / u16::from(color.channel_count()),
+    has_alpha: color.has_alpha(),
+    file_size,
+    unique_colors: unique_colors(&img),
+    mean_color: histogram::compute(&img)
+      .iter()
+      .filter(|histogram| histogram.channel != "luminance")
+      .map(|histogram| histogram.mean())
+      .collect(),
+  })
+}
+
+/// Counts the different RGBA colors in an image at its own bit depth.
+fn unique_colors(img: &DynamicImage) -> usize {
+  img
+    .to_rgba16()
+    .pixels()
+    .map(|pixel| pixel.0)
+    .collect::<HashSet<[u16; 4]>>()
+    .len()
+}
+
+/// A byte count, with the size in the largest binary unit it reaches.
+fn file_size(bytes: u64) -> String {
+  let units = ["KiB", "MiB", "GiB"];
+  let mut size = bytes as f64;
+  let mut unit = None;
+  for name in units.iter() {
+    if size < 1024.0 {
+      break;
+    }
+    size /= 1024.0;
+    unit = Some(name);
+  }
+
+  match unit {
+    Some(unit) => format!("{} bytes ({:.1} {})", bytes, size, unit),
+    None => format!("{} bytes", bytes),
+  }
+}
+
+/// Quotes and escapes a string for JSON.
+fn json_string(value: &str) -> String {
+  let mut quoted = String::from("\"");
+  for c in value.chars() {
+    match c {
+      '"' => quoted.push_str("\\\""),
+      '\\' => quoted.push_str("\\\\"),
+      '\n' => quoted.push_str("\\n"),
+      '\r' => quoted.push_str("\\r"),
+      '\t' => quoted.push_str("\\t"),
+      c if (c as u32) < 0x20 => quoted.push_str(&format!("\\u{:04x}", c as u32)),
+      c => quoted.push(c),
+    }
+  }
+  quoted.push('"');
+
+  quoted
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use image::{ImageBuffer, Rgb};
+
+  #[test]
+  fn json_strings_escape_quotes_backslashes_and_control_characters() {
+    assert_eq!(json_string("plain.png"), r#""plain.png""#);
+    assert_eq!(
+      json_string("say \"hi\"\\C:\\photos"),
+      r#""say \"hi\"\\C:\\photos""#
+    );
+    assert_eq!(json_string("a\nb\tc\rd"), r#""a\nb\tc\rd""#);
+    assert_eq!(json_string("bell\u{7}\u{1f}"), r#""bell\u0007\u001f""#);
+    assert_eq!(json_string("café"), "\"café\"");
+  }
+
+  #[test]
+  fn a_16_bit_image_is_described_exactly() {
+    let path = std::env::temp_dir()
+      .join(format!("image_utils_info_{}.png", std::process::id()))
+      .to_string_lossy()
+      .into_owned();
+    let pixels = vec![0, 65535, 0, 65535, 65535, 0];
+    DynamicImage::ImageRgb16(ImageBuffer::<Rgb<u16>, _>::from_raw(2, 1, pixels).unwrap())
+      .save(&path)
+      .unwrap();
+    let info = inspect(path.clone());
+    let size = fs::metadata(&path).unwrap().len();
+    fs::remove_file(&path).unwrap();
+    let info = info.unwrap();
+
+    assert_eq!(
+      info.to_text(),
+      format!(
+        "file:           {}\n\
+         format:         png\n\
+         dimensions:     2 x 1\n\
+         color type:     Rgb16\n\
+         bit depth:      16\n\
+         alpha:          no\n\
+         file size:      {} bytes\n\
+         unique colors:  2\n\
+         mean color:     127.5, 255.0, 0.0\n",
+        path, size
+      )
+    );
+    assert_eq!(
+      info.to_json(),
+      format!(
+        "{{\n  \"path\": {},\n  \"format\": \"png\",\n  \"width\": 2,\n  \"height\": 1,\n  \
+         \"color_type\": \"Rgb16\",\n  \"bit_depth\": 16,\n  \"has_alpha\": false,\n  \
+         \"file_size\": {},\n  \"unique_colors\": 2,\n  \"mean_color\": [127.5, 255, 0]\n}}\n",
+        json_string(&path),
+        size
+      )
+    );
+  }
+
+  #[test]
+  fn file_sizes_use_the_largest_unit_they_reach() {
+    assert_eq!(file_size(1023), "1023 bytes");
+    assert_eq!(file_size(1536), "1536 bytes (1.5 KiB)");
+    assert_eq!(file_size(3 << 30), "3221225472 bytes (3.0 GiB)");
+  }
+}
@@ -1,62 +1,36 @@
-use image::{DynamicImage, ImageBuffer};
-use num_complex::Complex;
-use std::process::exit;
+use encode::SaveOptions;
+use image::codecs::gif::{GifEncoder, Repeat};
+use image::codecs::jpeg::JpegEncoder;
+use image::codecs::png::PngEncoder;
+use image::{
+  Delay, DynamicImage, Frame, GenericImageView, ImageEncoder, ImageError, ImageFormat, ImageResult,
+};
+use std::fs::File;
+use std::io::BufWriter;
 use std::str::FromStr;
 
-/// Checks if a command was passed the correct number of arguments.
-///
-/// Arguments:
-///
-/// * `args` - &Vec<String>
-/// * `subcommand` - &str
-/// * `expected_length` - usize
-///
-/// Usage: ```check_for_invalid_args(args, "crop", 6);```
-pub fn check_for_invalid_args(
-  args: &Vec<String>,
-  subcommand: &str,
-  expected_length: usize,
-) -> Option<()> {
-  if args.is_empty() || args.len() != expected_length {
-    println!(
-      "\n\x1b[31m[ERROR]: Invalid \x1b[1m{0}\x1b[0m\x1b[31m arguments. Expected \x1b[1m{1}\x1b[0m\x1b[31m argument(s), but received \x1b[1m{2}\x1b[0m\x1b[31m. See the \x1b[1m{0}\x1b[0m\x1b[31m subcommand help for more assitance:\x1b[0m\n",
-      subcommand,
-      expected_length,
-      args.len()
-    );
-    print_commands();
-    return None;
-  };
-
-  return Some(());
-}
-
-/// Prints commands and their arguments.
-pub fn print_commands() {
-  println!("subcommand   <arguments>\n");
-  println!("blur         <amount(f32)> <input(String)> <output(String)>");
-  println!("brighten     <amount(i32)> <input(String)> <output(String)>");
-  println!(
-    "crop         <x(u32)> <y(u32)> <width(u32)> <height(u32)> <input(String)> <output(String)>"
-  );
-  println!("fractal      <output(String)>");
-  println!(
-    "generate     <width(u32)> <height(u32)> <red(u8)> <green(u8)> <blue(u8)> <output(String)>"
-  );
-  println!("grayscale    <input(String)> <output(String)>");
-  println!("invert       <input(String)> <output(String)>");
-  println!("rotate       <amount(i32 -> 90 | 180 | 270)> <input(String)> <output(String)>");
-  println!("");
-}
-
-/// Prints application usage information.
-pub fn print_usage_and_exit() {
-  println!("\nImage Manipulator");
-  println!("Matt Carlotta <matt@mattcarlotta.sh>");
-  println!("Manipulates images using the CLI\n");
-  print_commands();
-  exit(1);
-}
+pub mod adjust;
+pub mod batch;
+pub mod edge;
+pub mod encode;
+mod error;
+pub mod filter;
+pub mod fractal;
+pub mod histogram;
+pub mod info;
+pub mod noise;
+pub mod op;
+pub mod ops;
+pub mod palette;
+pub mod pattern;
+pub mod registry;
+pub mod render;
+pub mod transform;
+
+pub use error::{ImageUtilsError, Result};
+pub use op::{ArgKind, ArgSpec, Command, ImageOp, OpArgs};
+pub use registry::Registry;
+pub use transform::{Axis, CropRect, MirrorRegion, ResizeMode, RotateOptions, Rotation, Sampling};
 
 /// Open a file image.
 ///
@@ -64,278 +38,181 @@ pub fn print_usage_and_exit() {
 ///
 /// * `input` - String
 ///
-/// Usage: ```open_image("input.png");```
-pub fn open_image(input: String) -> DynamicImage {
-  image::open(input).expect("Failed to open input.")
+/// Usage: ```open_image("input.png")?;```
+pub fn open_image(input: String) -> Result<DynamicImage> {
+  image::open(&input).map_err(|error| match error {
+    ImageError::IoError(source) => ImageUtilsError::Io {
+      path: input,
+      source,
+    },
+    source => ImageUtilsError::Decode {
+      path: input,
+      source,
+    },
+  })
 }
 
-/// Saves a dynamic image.
+/// Saves a dynamic image, picking the format from the output's extension.
 ///
 /// Arguments:
 ///
 /// * `img` - DynamicImage
 /// * `output` - String
 ///
-/// Usage: ```save_image(image, "output.png");```
-pub fn save_image(img: DynamicImage, output: String) {
-  img.save(output).expect("Failed writing output.");
-
-  exit(0);
+/// Usage: ```save_image(image, "output.png")?;```
+pub fn save_image(img: DynamicImage, output: String) -> Result<()> {
+  save_image_with(img, output, &SaveOptions::default())
 }
 
-/// Dynamically parses a string to a number type for a `command` and `property`.
+/// Saves a dynamic image with explicit encoder `options`.
 ///
 /// Arguments:
 ///
-/// * `subcommand` - String
-/// * `property` - String
-/// * `num_str` - String
-///
-/// Usage: ```parse_number("crop", "x", "255");```
-pub fn parse_number<T: FromStr>(subcommand: &str, property: &str, num_str: String) -> T {
-  num_str.parse::<T>().unwrap_or_else(|_error| {
-    println!(
-      "\n\x1b[31m[ERROR]: The \x1b[1m<{0}>\x1b[0m\x1b[31m argument passed to \x1b[1m{1}\x1b[0m\x1b[31m is an invalid number. See the \x1b[1m{1}\x1b[0m\x1b[31m subcommand help for more assitance:\x1b[0m\n", property, subcommand
-    );
-
-    print_commands();
-
-    exit(1);
-  })
-}
-
-/// Blurs an image by an `amount`.
-///
-/// Arguments:
-///
-/// * `amount` - u32
-/// * `input` - String
+/// * `img` - DynamicImage
 /// * `output` - String
+/// * `options` - &SaveOptions
 ///
-/// Usage: ```blur 100 input.png output.png```
-pub fn blur(args: &mut Vec<String>) {
-  match check_for_invalid_args(&args, "blur", 3) {
-    Some(()) => {
-      let amount = parse_number::<f32>("blur", "amount", args.remove(0));
-      // Here's how you open an existing image file
-      let img = open_image(args.remove(0));
-      // **OPTION**
-      // Parse the blur amount (an f32) from the command-line and pass it through
-      // to this function, instead of hard-coding it to 2.0.
-      let new_image = img.blur(amount);
-      // Here's how you save an image to a file.
-      save_image(new_image, args.remove(0));
-    }
-    None => exit(1),
-  }
-}
+/// Usage: ```save_image_with(image, "output", &SaveOptions { format: Some(ImageFormat::Jpeg), ..SaveOptions::default() })?;```
+pub fn save_image_with(img: DynamicImage, output: String, options: &SaveOptions) -> Result<()> {
+  let img = match options.color {
+    Some(color) => encode::convert(img, color),
+    None => img,
+  };
+  let format = match options.format {
+    Some(format) => format,
+    None => ImageFormat::from_path(&output).map_err(|error| save_error(output.clone(), error))?,
+  };
 
-/// Brightens an image by an `amount`.
-///
-/// Arguments:
-///
-/// * `amount` - u32
-/// * `input` - String
-/// * `output` - String
-///
-/// Usage: ```brighten 100 input.png output.png```
-pub fn brighten(args: &mut Vec<String>) {
-  match check_for_invalid_args(&args, "brighten", 3) {
-    Some(()) => {
-      let amount = parse_number::<i32>("brighten", "amount", args.remove(0));
-      // See blur() for an example of how to open / save an image.
-      let img = open_image(args.remove(0));
-      // .brighten() takes one argument, an i32.  Positive numbers brighten the
-      // image. Negative numbers darken it.  It returns a new image.
-      let new_image = img.brighten(amount);
-      // Challenge: parse the brightness amount from the command-line and pass it
-      // through to this function.
-      save_image(new_image, args.remove(0));
-    }
-    None => exit(1),
-  }
+  encode(&img, &output, format, options).map_err(|error| save_error(output, error))
 }
 
-/// Crops an image by `x`, `y`, `width` and `height`.
-///
-/// Arguments:
-///
-/// * `x` - u32
-/// * `y` - u32
-/// * `width` - u32
-/// * `height` - u32
-/// * `input` - String
-/// * `output` - String
-///
-/// Usage: ```crop 0 0 640 480 input.png output.png```
-pub fn crop(args: &mut Vec<String>) {
-  match check_for_invalid_args(&args, "crop", 6) {
-    Some(()) => {
-      let [x, y, width, height] =
-        ["x", "y", "width", "height"].map(|opt| parse_number::<u32>("crop", opt, args.remove(0)));
-
-      // See blur() for an example of how to open an image.
-      let mut img = open_image(args.remove(0));
-      // .crop() takes four arguments: x: u32, y: u32, width: u32, height: u32
-      // You may hard-code them, if you like.  It returns a new image.
-      let new_image = img.crop(x, y, width, height);
-      // Challenge: parse the four values from the command-line and pass them
-      // through to this function.
-
-      save_image(new_image, args.remove(0));
+/// Writes `img` to `output` as a `format`, applying the encoder options that format supports.
+fn encode(
+  img: &DynamicImage,
+  output: &str,
+  format: ImageFormat,
+  options: &SaveOptions,
+) -> ImageResult<()> {
+  let (width, height) = img.dimensions();
+  match format {
+    ImageFormat::Png => {
+      let file = BufWriter::new(File::create(output)?);
+      PngEncoder::new_with_quality(file, options.png_compression, options.png_filter).write_image(
+        img.as_bytes(),
+        width,
+        height,
+        img.color(),
+      )
     }
-    None => exit(1),
-  }
-}
-
-/// Generates a fractal image.
-///
-/// Arguments:
-///
-/// * `output` - String
-///
-/// Usage: ```fractal output.png```
-pub fn fractal(args: &mut Vec<String>) {
-  match check_for_invalid_args(&args, "fractal", 1) {
-    Some(()) => {
-      let width = 800;
-      let height = 800;
-
-      let mut imgbuf = ImageBuffer::new(width, height);
-
-      let scale_x = 3.0 / width as f32;
-      let scale_y = 3.0 / height as f32;
-
-      // Iterate over the coordinates and pixels of the image
-      for (x, y, pixel) in imgbuf.enumerate_pixels_mut() {
-        // Use red and blue to be a pretty gradient background
-        let red = (0.3 * x as f32) as u8;
-        let blue = (0.3 * y as f32) as u8;
-
-        // Use green as the fractal foreground (here is the fractal math part)
-        let cx = y as f32 * scale_x - 1.5;
-        let cy = x as f32 * scale_y - 1.5;
-
-        let c = Complex::new(-0.4, 0.6);
-        let mut z = Complex::new(cx, cy);
-
-        let mut green = 0;
-        while green < 255 && z.norm() <= 2.0 {
-          z = z * z + c;
-          green += 1;
-        }
-
-        // Actually set the pixel. red, green, and blue are u8 values!
-        *pixel = image::Rgb([red, green, blue]);
-      }
-
-      imgbuf.save(args.remove(0)).unwrap();
+    ImageFormat::Jpeg => {
+      let mut file = BufWriter::new(File::create(output)?);
+      JpegEncoder::new_with_quality(&mut file, options.jpeg_quality).write_image(
+        img.as_bytes(),
+        width,
+        height,
+        img.color(),
+      )
     }
-    None => exit(1),
+    // No other format has options worth exposing.
+    _ => img.save_with_format(output, format),
   }
 }
 
-/// Generates an image by `width`, `height`, `red`, `green` and `blue`.
+/// Saves images as the frames of a looping animated GIF, showing each for `delay_ms`
+/// milliseconds. Frames are encoded as they're pulled from `frames`, so a lazy iterator keeps only
+/// one in memory at a time.
 ///
 /// Arguments:
 ///
-/// * `width` - u32
-/// * `height` - u32
-/// * `red` - u8
-/// * `green` - u8
-/// * `blue` - u8
+/// * `frames` - impl IntoIterator<Item = DynamicImage>
+/// * `delay_ms` - u32
 /// * `output` - String
 ///
-/// Usage: ```generate 640 480 0 0 0 input.png output.png```
-pub fn generate(args: &mut Vec<String>) {
-  match check_for_invalid_args(&args, "rotate", 6) {
-    Some(()) => {
-      let [width, height] =
-        ["width", "height"].map(|d| parse_number::<u32>("generate", d, args.remove(0)));
-
-      let [red, green, blue]: [u8; 3] =
-        ["red", "green", "blue"].map(|c| parse_number::<u8>("generate", c, args.remove(0)));
-
-      let mut imgbuf = ImageBuffer::new(width, height);
-
-      for (_x, _y, pixel) in imgbuf.enumerate_pixels_mut() {
-        // Actually set the pixel. red, green, and blue are u8 values!
-        *pixel = image::Rgb([red, green, blue]);
-      }
+/// Usage: ```save_animation(frames, 100, "output.gif")?;```
+pub fn save_animation(
+  frames: impl IntoIterator<Item = DynamicImage>,
+  delay_ms: u32,
+  output: String,
+) -> Result<()> {
+  let file = File::create(&output).map_err(|source| ImageUtilsError::Io {
+    path: output.clone(),
+    source,
+  })?;
+  // Speed 10 quantizes colors much faster than the default at a barely visible cost.
+  let mut encoder = GifEncoder::new_with_speed(file, 10);
+  let delay = Delay::from_numer_denom_ms(delay_ms, 1);
+
+  encoder
+    .set_repeat(Repeat::Infinite)
+    .and_then(|_| {
+      encoder.encode_frames(
+        frames
+          .into_iter()
+          .map(|frame| Frame::from_parts(frame.into_rgba8(), 0, 0, delay)),
+      )
+    })
+    .map_err(|error| save_error(output, error))
+}
 
-      imgbuf.save(args.remove(0)).unwrap();
-    }
-    None => exit(1),
+/// Maps an error from encoding an image to `output` to an `ImageUtilsError`.
+fn save_error(output: String, error: ImageError) -> ImageUtilsError {
+  match error {
+    ImageError::IoError(source) => ImageUtilsError::Io {
+      path: output,
+      source,
+    },
+    source => ImageUtilsError::Encode {
+      path: output,
+      source,
+    },
   }
 }
 
-/// Grayscales an image.
+/// Dynamically parses a string to a number type for a `command` and `property`.
 ///
 /// Arguments:
 ///
-/// * `input` - String
-/// * `output` - String
+/// * `subcommand` - String
+/// * `property` - String
+/// * `num_str` - String
 ///
-/// Usage: ```grayscale input.png output.png```
-pub fn grayscale(args: &mut Vec<String>) {
-  match check_for_invalid_args(&args, "grayscale", 2) {
-    Some(()) => {
-      let img = open_image(args.remove(0));
-
-      // .grayscale() takes no arguments. It returns a new image.
-      let new_image = img.grayscale();
-      save_image(new_image, args.remove(0));
-    }
-    None => exit(1),
-  }
+/// Usage: ```parse_number::<u32>("crop", "x", "255")?;```
+pub fn parse_number<T: FromStr>(subcommand: &str, property: &str, num_str: String) -> Result<T> {
+  num_str
+    .parse::<T>()
+    .map_err(|_error| ImageUtilsError::bad_argument(subcommand, property, "is an invalid number"))
 }
 
-/// Invert an image.
+/// Parses an angle in degrees, failing on anything that isn't a finite number.
 ///
 /// Arguments:
 ///
-/// * `input` - String
-/// * `output` - String
-///
-/// Usage: ```invert input.png output.png```
-pub fn invert(args: &mut Vec<String>) {
-  match check_for_invalid_args(&args, "invert", 2) {
-    Some(()) => {
-      let mut img = open_image(args.remove(0));
-
-      // .invert() takes no arguments and converts the image in-place, so you
-      // will use the same image to save out to a different file.
-      img.invert();
-      save_image(img, args.remove(0));
-    }
-    None => exit(1),
+/// * `subcommand` - &str
+/// * `property` - &str
+/// * `degrees` - String
+///
+/// Usage: ```parse_angle("rotate", "amount", "22.5".to_string())?;```
+pub fn parse_angle(subcommand: &str, property: &str, degrees: String) -> Result<f64> {
+  match degrees.trim().parse::<f64>() {
+    Ok(angle) if angle.is_finite() => Ok(angle),
+    _ => Err(ImageUtilsError::bad_argument(
+      subcommand,
+      property,
+      "is not a valid angle in degrees",
+    )),
   }
 }
 
-/// Rotates an image.
+/// Resolves a requested number of worker threads, where `0` means one per available core.
 ///
 /// Arguments:
 ///
-/// * `rotate` - u32 (90, 180, 270)
-/// * `input` - String
-/// * `output` - String
+/// * `threads` - usize
 ///
-/// Usage: ```rotate input.png output.png```
-pub fn rotate(args: &mut Vec<String>) {
-  match check_for_invalid_args(&args, "rotate", 3) {
-    Some(()) => {
-      let rotate = args.remove(0);
-      let img = open_image(args.remove(0));
-
-      let new_image = match rotate.as_str() {
-        "90" => img.rotate90(),
-        "180" => img.rotate180(),
-        "270" => img.rotate270(),
-        _ => img.rotate90(),
-      };
-
-      save_image(new_image, args.remove(0));
-    }
-    None => exit(1),
+/// Usage: ```resolve_threads(0);```
+pub fn resolve_threads(threads: usize) -> usize {
+  match threads {
+    0 => std::thread::available_parallelism().map_or(1, |count| count.get()),
+    threads => threads,
   }
 }
@@ -0,0 +1,297 @@
+use crate::palette::Palette;
+use crate::render::par_render;
+use image::DynamicImage;
+use std::fmt;
+use std::str::FromStr;
+
+/// The size of the lattice noise repeats over.
+const LATTICE: usize = 256;
+
+/// The noise every octave is built from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Basis {
+  /// Random values at every lattice point, smoothly interpolated. Blocky at low octaves.
+  Value,
+  /// Random gradients at every lattice point, which gives rounder, more natural features.
+  Perlin,
+}
+
+impl Basis {
+  /// The names `Basis::from_str` accepts.
+  pub const NAMES: [&'static str; 2] = ["value", "perlin"];
+}
+
+impl FromStr for Basis {
+  type Err = String;
+
+  fn from_str(name: &str) -> Result<Self, Self::Err> {
+    match name {
+      "value" => Ok(Basis::Value),
+      "perlin" => Ok(Basis::Perlin),
+      _ => Err(format!("must be one of {}", Basis::NAMES.join(", "))),
+    }
+  }
+}
+
+impl fmt::Display for Basis {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      Basis::Value => write!(f, "value"),
+      Basis::Perlin => write!(f, "perlin"),
+    }
+  }
+}
+
+/// A seeded lattice of random values and gradients. The same seed always produces the same noise,
+/// on every platform and with any version of the dependencies, since the lattice comes from
+/// `SplitMix64` rather than a library generator.
+#[derive(Debug, Clone)]
+pub struct Noise {
+  /// A shuffled `0..LATTICE`, repeated twice so lookups can skip wrapping.
+  permutation: Vec<usize>,
+  values: Vec<f64>,
+}
+
+impl Noise {
+  /// Builds the lattice for a `seed`.
+  ///
+  /// Arguments:
+  ///
+  /// * `seed` - u64
+  ///
+  /// Usage: ```Noise::new(42);```
+  pub fn new(seed: u64) -> Self {
+    let mut rng = SplitMix64(seed);
+    let mut permutation: Vec<usize> = (0..LATTICE).collect();
+    // Fisher-Yates shuffle.
+    for index in (1..LATTICE).rev() {
+      permutation.swap(index, rng.below(index + 1));
+    }
+    permutation.extend_from_within(..);
+    let values = (0..LATTICE).map(|_| rng.unit() * 2.0 - 1.0).collect();
+
+    Noise {
+      permutation,
+      values,
+    }
+  }
+
+  /// Samples a single octave of `basis` noise at (`x`, `y`), roughly between -1.0 and 1.0.
+  ///
+  /// Arguments:
+  ///
+  /// * `basis` - Basis
+  /// * `x` - f64
+  /// * `y` - f64
+  ///
+  /// Usage: ```noise.sample(Basis::Perlin, 1.5, 2.25);```
+  pub fn sample(&self, basis: Basis, x: f64, y: f64) -> f64 {
+    let (x0, y0) = (x.floor(), y.floor());
+    let (fx, fy) = (x - x0, y - y0);
+    let (cx, cy) = (lattice(x0), lattice(y0));
+    let corner = |dx: usize, dy: usize| {
+      let hash = self.permutation[self.permutation[cx + dx] + cy + dy];
+      match basis {
+        Basis::Value => self.values[hash],
+        // Dot the offset from the corner with one of four diagonal gradients.
+        Basis::Perlin => {
+          let (gx, gy) = match hash & 3 {
+            0 => (1.0, 1.0),
+            1 => (-1.0, 1.0),
+            2 => (1.0, -1.0),
+            _ => (-1.0, -1.0),
+          };
+          gx * (fx - dx as f64) + gy * (fy - dy as f64)
+        }
+      }
+    };
+
+    let (u, v) = (fade(fx), fade(fy));
+    let top = lerp(corner(0, 0), corner(1, 0), u);
+    let bottom = lerp(corner(0, 1), corner(1, 1), u);
+
+    lerp(top, bottom, v)
+  }
+
+  /// Sums `octaves` layers of noise (fractal Brownian motion). Every octave is `lacunarity` times
+  /// finer and `persistence` times fainter than the last. The result is between 0.0 and 1.0.
+  ///
+  /// Arguments:
+  ///
+  /// * `basis` - Basis
+  /// * `x` - f64
+  /// * `y` - f64
+  /// * `octaves` - u32
+  /// * `persistence` - f64
+  /// * `lacunarity` - f64
+  ///
+  /// Usage: ```noise.fbm(Basis::Perlin, 1.5, 2.25, 4, 0.5, 2.0);```
+  pub fn fbm(
+    &self,
+    basis: Basis,
+    x: f64,
+    y: f64,
+    octaves: u32,
+    persistence: f64,
+    lacunarity: f64,
+  ) -> f64 {
+    let (mut total, mut range) = (0.0, 0.0);
+    let (mut amplitude, mut frequency) = (1.0, 1.0);
+    for _ in 0..octaves {
+      total += self.sample(basis, x * frequency, y * frequency) * amplitude;
+      range += amplitude;
+      amplitude *= persistence;
+      frequency *= lacunarity;
+    }
+
+    if range > 0.0 {
+      (total / range + 1.0) / 2.0
+    } else {
+      0.5
+    }
+  }
+}
+
+/// The SplitMix64 generator (Steele, Lea and Flood, 2014). Small, fast and fully specified, so a
+/// seed gives the same sequence everywhere.
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+  fn next_u64(&mut self) -> u64 {
+    self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    let mut z = self.0;
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+
+    z ^ (z >> 31)
+  }
+
+  /// A number in `0..bound`, by scaling rather than a biased modulo.
+  fn below(&mut self, bound: usize) -> usize {
+    ((u128::from(self.next_u64()) * bound as u128) >> 64) as usize
+  }
+
+  /// A number in `0.0..1.0` from the top 53 bits.
+  fn unit(&mut self) -> f64 {
+    (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+  }
+}
+
+/// Wraps a lattice coordinate into `0..LATTICE`.
+fn lattice(coordinate: f64) -> usize {
+  (coordinate as i64).rem_euclid(LATTICE as i64) as usize
+}
+
+/// Eases `t` so the noise is smooth across lattice cells.
+fn fade(t: f64) -> f64 {
+  t * t * t * (t * (t * 6.0 - 15.0) + 10.0)
+}
+
+fn lerp(a: f64, b: f64, t: f64) -> f64 {
+  a + (b - a) * t
+}
+
+/// Everything that determines what a noise texture looks like.
+#[derive(Debug, Clone, PartialEq)]
+pub struct NoiseParams {
+  pub basis: Basis,
+  pub width: u32,
+  pub height: u32,
+  pub seed: u64,
+  /// The size of the coarsest features in pixels.
+  pub scale: f64,
+  pub octaves: u32,
+  pub persistence: f64,
+  pub lacunarity: f64,
+  pub palette: Palette,
+}
+
+/// Renders a noise texture through its palette, spreading rows over `threads` workers.
+///
+/// Arguments:
+///
+/// * `params` - &NoiseParams
+/// * `threads` - usize (0 uses every core)
+///
+/// Usage: ```noise::render(&params, 0);```
+pub fn render(params: &NoiseParams, threads: usize) -> DynamicImage {
+  let noise = Noise::new(params.seed);
+
+  DynamicImage::ImageRgb8(par_render(params.width, params.height, threads, |x, y| {
+    let t = noise.fbm(
+      params.basis,
+      f64::from(x) / params.scale,
+      f64::from(y) / params.scale,
+      params.octaves,
+      params.persistence,
+      params.lacunarity,
+    );
+
+    params.palette.sample(t)
+  }))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn params(seed: u64) -> NoiseParams {
+    NoiseParams {
+      basis: Basis::Perlin,
+      width: 6,
+      height: 2,
+      seed,
+      scale: 2.5,
+      octaves: 3,
+      persistence: 0.5,
+      lacunarity: 2.0,
+      palette: "grayscale".parse().unwrap(),
+    }
+  }
+
+  #[test]
+  fn splitmix64_matches_the_reference_sequence() {
+    let mut rng = SplitMix64(1_234_567);
+    let outputs: Vec<u64> = (0..5).map(|_| rng.next_u64()).collect();
+
+    assert_eq!(
+      outputs,
+      [
+        6_457_827_717_110_365_317,
+        3_203_168_211_198_807_973,
+        9_817_491_932_198_370_423,
+        4_593_380_528_125_082_431,
+        16_408_922_859_458_223_821,
+      ]
+    );
+  }
+
+  #[test]
+  fn a_seed_always_renders_the_same_bytes() {
+    let rendered = render(&params(42), 1);
+    // The grayscale palette repeats every level over red, green and blue.
+    let levels = [128, 118, 117, 132, 84, 128, 161, 147, 173, 166, 132, 135];
+    let expected: Vec<u8> = levels.iter().flat_map(|&level| [level; 3]).collect();
+
+    assert_eq!(rendered.as_bytes(), &expected[..]);
+    assert_eq!(render(&params(42), 3).as_bytes(), rendered.as_bytes());
+  }
+
+  #[test]
+  fn different_seeds_render_different_noise() {
+    assert_ne!(
+      render(&params(1), 1).as_bytes(),
+      render(&params(2), 1).as_bytes()
+    );
+  }
+
+  #[test]
+  fn the_permutation_holds_every_lattice_point_twice() {
+    let noise = Noise::new(7);
+    let mut first: Vec<usize> = noise.permutation[..LATTICE].to_vec();
+    first.sort_unstable();
+
+    assert_eq!(first, (0..LATTICE).collect::<Vec<usize>>());
+    assert_eq!(noise.permutation[..LATTICE], noise.permutation[LATTICE..]);
+  }
+}
@@ -0,0 +1,449 @@
+use crate::{parse_number, ImageUtilsError, Result};
+use image::DynamicImage;
+use std::fmt;
+use std::str::FromStr;
+
+/// The type an argument must parse as.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArgKind {
+  F32,
+  F64,
+  I32,
+  U32,
+  U64,
+  U8,
+  Bool,
+  Text,
+}
+
+impl ArgKind {
+  /// Checks that `value` parses as this kind of argument.
+  ///
+  /// Arguments:
+  ///
+  /// * `value` - &str
+  ///
+  /// Usage: ```ArgKind::U32.accepts("640");```
+  pub fn accepts(self, value: &str) -> bool {
+    match self {
+      ArgKind::F32 => value.parse::<f32>().is_ok(),
+      ArgKind::F64 => value.parse::<f64>().is_ok(),
+      ArgKind::I32 => value.parse::<i32>().is_ok(),
+      ArgKind::U32 => value.parse::<u32>().is_ok(),
+      ArgKind::U64 => value.parse::<u64>().is_ok(),
+      ArgKind::U8 => value.parse::<u8>().is_ok(),
+      ArgKind::Bool => value.parse::<bool>().is_ok(),
+      ArgKind::Text => true,
+    }
+  }
+}
+
+impl fmt::Display for ArgKind {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    let name = match self {
+      ArgKind::F32 => "f32",
+      ArgKind::F64 => "f64",
+      ArgKind::I32 => "i32",
+      ArgKind::U32 => "u32",
+      ArgKind::U64 => "u64",
+      ArgKind::U8 => "u8",
+      ArgKind::Bool => "bool",
+      ArgKind::Text => "String",
+    };
+
+    write!(f, "{}", name)
+  }
+}
+
+/// An argument an operation expects. Arguments without a default are positional; arguments with
+/// a default are options, passed anywhere as `--name=value`. Optional positional arguments are
+/// filled together: either every one of them is passed or none are.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ArgSpec {
+  pub name: &'static str,
+  pub kind: ArgKind,
+  pub default: Option<&'static str>,
+  pub optional: bool,
+}
+
+impl ArgSpec {
+  /// Creates a positional argument called `name` that parses as `kind`.
+  ///
+  /// Arguments:
+  ///
+  /// * `name` - &'static str
+  /// * `kind` - ArgKind
+  ///
+  /// Usage: ```ArgSpec::new("amount", ArgKind::F32);```
+  pub const fn new(name: &'static str, kind: ArgKind) -> Self {
+    ArgSpec {
+      name,
+      kind,
+      default: None,
+      optional: false,
+    }
+  }
+
+  /// Creates a positional argument called `name` that parses as `kind` and can be left out.
+  ///
+  /// Arguments:
+  ///
+  /// * `name` - &'static str
+  /// * `kind` - ArgKind
+  ///
+  /// Usage: ```ArgSpec::optional("red", ArgKind::U8);```
+  pub const fn optional(name: &'static str, kind: ArgKind) -> Self {
+    ArgSpec {
+      name,
+      kind,
+      default: None,
+      optional: true,
+    }
+  }
+
+  /// Creates a `--name=value` option that parses as `kind` and falls back to `default`.
+  ///
+  /// Arguments:
+  ///
+  /// * `name` - &'static str
+  /// * `kind` - ArgKind
+  /// * `default` - &'static str
+  ///
+  /// Usage: ```ArgSpec::option("threads", ArgKind::U32, "0");```
+  pub const fn option(name: &'static str, kind: ArgKind, default: &'static str) -> Self {
+    ArgSpec {
+      name,
+      kind,
+      default: Some(default),
+      optional: false,
+    }
+  }
+
+  /// Whether the argument is a `--name=value` option rather than a positional argument.
+  pub fn is_option(&self) -> bool {
+    self.default.is_some()
+  }
+}
+
+impl fmt::Display for ArgSpec {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self.default {
+      Some(default) => write!(f, "[--{}({})={}]", self.name, self.kind, default),
+      None if self.optional => write!(f, "[<{}({})>]", self.name, self.kind),
+      None => write!(f, "<{}({})>", self.name, self.kind),
+    }
+  }
+}
+
+/// Whether a command-line token is a `--name=value` option.
+///
+/// Arguments:
+///
+/// * `token` - &str
+///
+/// Usage: ```is_option("--threads=4");```
+pub fn is_option(token: &str) -> bool {
+  token.starts_with("--")
+}
+
+/// Arguments that have been validated against an operation's `ArgSpec`s.
+#[derive(Debug, Clone)]
+pub struct OpArgs {
+  subcommand: String,
+  values: Vec<(&'static str, String)>,
+}
+
+impl OpArgs {
+  /// Validates `values` against `specs`, pairing each value with the argument it fills. Options
+  /// that weren't passed take their default; optional positional arguments that weren't passed
+  /// are left out.
+  ///
+  /// Arguments:
+  ///
+  /// * `subcommand` - &str
+  /// * `specs` - &[ArgSpec]
+  /// * `values` - Vec<String>
+  ///
+  /// Usage: ```OpArgs::parse("blur", Blur.args(), vec!["2.5".to_string()])?;```
+  pub fn parse(subcommand: &str, specs: &[ArgSpec], values: Vec<String>) -> Result<Self> {
+    let (options, positionals): (Vec<String>, Vec<String>) =
+      values.into_iter().partition(|value| is_option(value));
+    let positional_specs: Vec<&ArgSpec> = specs.iter().filter(|spec| !spec.is_option()).collect();
+    let required: Vec<&ArgSpec> = positional_specs
+      .iter()
+      .copied()
+      .filter(|spec| !spec.optional)
+      .collect();
+    let required = if positionals.len() > required.len() {
+      positional_specs
+    } else {
+      required
+    };
+    if positionals.len() != required.len() {
+      return Err(ImageUtilsError::ArgumentCount {
+        subcommand: subcommand.to_string(),
+        expected: required.len(),
+        received: positionals.len(),
+      });
+    }
+
+    let mut paired: Vec<(&ArgSpec, String)> = required.into_iter().zip(positionals).collect();
+    for option in options {
+      let body = &option[2..];
+      let (name, value) = match body.find('=') {
+        Some(index) => (&body[..index], &body[index + 1..]),
+        None => (body, ""),
+      };
+      let spec = specs
+        .iter()
+        .find(|spec| spec.is_option() && spec.name == name)
+        .ok_or_else(|| {
+          ImageUtilsError::bad_argument(subcommand, &option, "is not a known option")
+        })?;
+      paired.retain(|(existing, _)| existing.name != spec.name);
+      paired.push((spec, value.to_string()));
+    }
+    for spec in specs {
+      if let Some(default) = spec.default {
+        if !paired
+          .iter()
+          .any(|(existing, _)| existing.name == spec.name)
+        {
+          paired.push((spec, default.to_string()));
+        }
+      }
+    }
+
+    let mut values = Vec::with_capacity(paired.len());
+    for (spec, value) in paired {
+      if !spec.kind.accepts(&value) {
+        return Err(ImageUtilsError::bad_argument(
+          subcommand,
+          spec.name,
+          format!("is not a valid {}", spec.kind),
+        ));
+      }
+      values.push((spec.name, value));
+    }
+
+    Ok(OpArgs {
+      subcommand: subcommand.to_string(),
+      values,
+    })
+  }
+
+  /// Fails on the first of `checks` that doesn't hold, naming its argument and giving the reason.
+  ///
+  /// Arguments:
+  ///
+  /// * `checks` - &[(&str, bool, &str)] (`(name, valid, reason)`)
+  ///
+  /// Usage: ```args.check(&[("width", width > 0, "must be greater than 0")])?;```
+  pub fn check(&self, checks: &[(&str, bool, &str)]) -> Result<()> {
+    match checks.iter().find(|(_, valid, _)| !valid) {
+      Some((name, _, reason)) => Err(ImageUtilsError::bad_argument(
+        &self.subcommand,
+        name,
+        *reason,
+      )),
+      None => Ok(()),
+    }
+  }
+
+  /// The subcommand or pipeline stage the arguments were parsed for.
+  pub fn subcommand(&self) -> &str {
+    &self.subcommand
+  }
+
+  /// Whether the argument called `name` was passed or has a default.
+  ///
+  /// Arguments:
+  ///
+  /// * `name` - &str
+  ///
+  /// Usage: ```args.has("red");```
+  pub fn has(&self, name: &str) -> bool {
+    self.values.iter().any(|(arg, _)| *arg == name)
+  }
+
+  /// Parses the argument called `name`.
+  ///
+  /// Arguments:
+  ///
+  /// * `name` - &str
+  ///
+  /// Usage: ```args.get::<f32>("amount")?;```
+  pub fn get<T: FromStr>(&self, name: &str) -> Result<T> {
+    let (_, value) = self
+      .values
+      .iter()
+      .find(|(arg, _)| *arg == name)
+      .ok_or_else(|| ImageUtilsError::bad_argument(&self.subcommand, name, "is missing"))?;
+
+    parse_number(&self.subcommand, name, value.clone())
+  }
+}
+
+/// An image-to-image operation. Every `ImageOp` is both a subcommand, run as
+/// `<name> <args...> <input> <output>`, and a pipeline stage.
+pub trait ImageOp: Send + Sync {
+  /// The subcommand and pipeline stage name.
+  fn name(&self) -> &'static str;
+
+  /// The arguments and options, excluding the input and output paths.
+  fn args(&self) -> &[ArgSpec];
+
+  /// Whether the command writes an image that the `SAVE_ARGS` output options apply to. The
+  /// registry adds those options to its arguments.
+  fn saves_image(&self) -> bool {
+    false
+  }
+
+  /// A one line description for the usage output.
+  fn help(&self) -> &'static str;
+
+  /// Applies the operation to an in-memory image.
+  fn apply(&self, img: &DynamicImage, args: &OpArgs) -> Result<DynamicImage>;
+}
+
+/// A subcommand that isn't an image-to-image operation, e.g. one that generates an image.
+pub trait Command: Send + Sync {
+  /// The subcommand name.
+  fn name(&self) -> &'static str;
+
+  /// Every argument and option, including any paths.
+  fn args(&self) -> &[ArgSpec];
+
+  /// Whether the command writes an image that the `SAVE_ARGS` output options apply to. The
+  /// registry adds those options to its arguments.
+  fn saves_image(&self) -> bool {
+    false
+  }
+
+  /// A one line description for the usage output.
+  fn help(&self) -> &'static str;
+
+  /// Runs the subcommand.
+  fn run(&self, args: &OpArgs) -> Result<()>;
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  const SPECS: [ArgSpec; 2] = [
+    ArgSpec::new("amount", ArgKind::F32),
+    ArgSpec::new("label", ArgKind::Text),
+  ];
+
+  fn strings(values: &[&str]) -> Vec<String> {
+    values.iter().map(|value| value.to_string()).collect()
+  }
+
+  #[test]
+  fn values_are_paired_with_their_specs_in_order() {
+    let args = OpArgs::parse("blur", &SPECS, strings(&["2.5", "soft"])).unwrap();
+
+    assert_eq!(args.get::<f32>("amount").unwrap(), 2.5);
+    assert_eq!(args.get::<String>("label").unwrap(), "soft");
+    assert!(args.get::<f32>("radius").is_err());
+  }
+
+  #[test]
+  fn the_wrong_number_of_values_is_an_error() {
+    for values in [strings(&["1"]), strings(&["1", "a", "b"])].iter() {
+      match OpArgs::parse("blur", &SPECS, values.clone()) {
+        Err(ImageUtilsError::ArgumentCount {
+          expected, received, ..
+        }) => {
+          assert_eq!(expected, 2);
+          assert_eq!(received, values.len());
+        }
+        other => panic!("expected an argument count error, got {:?}", other),
+      }
+    }
+  }
+
+  #[test]
+  fn options_go_anywhere_and_fall_back_to_their_defaults() {
+    let specs = [
+      ArgSpec::new("amount", ArgKind::F32),
+      ArgSpec::option("threads", ArgKind::U32, "0"),
+      ArgSpec::option("edge", ArgKind::Text, "clamp"),
+    ];
+    let args = OpArgs::parse("blur", &specs, strings(&["--threads=4", "2.5"])).unwrap();
+
+    assert_eq!(args.get::<f32>("amount").unwrap(), 2.5);
+    assert_eq!(args.get::<u32>("threads").unwrap(), 4);
+    assert_eq!(args.get::<String>("edge").unwrap(), "clamp");
+
+    let repeated = strings(&["1", "--threads=2", "--threads=8"]);
+    assert_eq!(
+      OpArgs::parse("blur", &specs, repeated)
+        .unwrap()
+        .get::<u32>("threads")
+        .unwrap(),
+      8
+    );
+    assert!(matches!(
+      OpArgs::parse("blur", &specs, strings(&["1", "--sigma=2"])),
+      Err(ImageUtilsError::BadArgument { ref argument, .. }) if argument == "--sigma=2"
+    ));
+    assert!(matches!(
+      OpArgs::parse("blur", &specs, strings(&["1", "--threads=-1"])),
+      Err(ImageUtilsError::BadArgument { ref argument, .. }) if argument == "threads"
+    ));
+  }
+
+  #[test]
+  fn checks_fail_on_the_first_that_does_not_hold() {
+    let args = OpArgs::parse("blur", &SPECS, strings(&["2.5", "soft"])).unwrap();
+
+    assert!(args.check(&[("amount", true, "is fine")]).is_ok());
+    assert!(matches!(
+      args.check(&[
+        ("amount", true, "is fine"),
+        ("label", false, "is too soft"),
+        ("amount", false, "is too much"),
+      ]),
+      Err(ImageUtilsError::BadArgument { ref subcommand, ref argument, ref reason })
+        if subcommand == "blur" && argument == "label" && reason == "is too soft"
+    ));
+  }
+
+  #[test]
+  fn values_of_the_wrong_kind_are_rejected() {
+    assert!(matches!(
+      OpArgs::parse("blur", &SPECS, strings(&["lots", "soft"])),
+      Err(ImageUtilsError::BadArgument { ref argument, .. }) if argument == "amount"
+    ));
+    assert!(!ArgKind::U8.accepts("256"));
+    assert!(!ArgKind::U32.accepts("-1"));
+    assert!(ArgKind::I32.accepts("-1"));
+  }
+
+  #[test]
+  fn optional_positionals_are_filled_all_or_none() {
+    let specs = [
+      ArgSpec::new("width", ArgKind::U32),
+      ArgSpec::optional("red", ArgKind::U8),
+      ArgSpec::optional("green", ArgKind::U8),
+    ];
+
+    let without = OpArgs::parse("generate", &specs, strings(&["10"])).unwrap();
+    assert!(!without.has("red") && !without.has("green"));
+
+    let with = OpArgs::parse("generate", &specs, strings(&["10", "1", "2"])).unwrap();
+    assert_eq!(with.get::<u8>("red").unwrap(), 1);
+    assert_eq!(with.get::<u8>("green").unwrap(), 2);
+
+    assert!(matches!(
+      OpArgs::parse("generate", &specs, strings(&["10", "1"])),
+      Err(ImageUtilsError::ArgumentCount {
+        expected: 3,
+        received: 2,
+        ..
+      })
+    ));
+  }
+}
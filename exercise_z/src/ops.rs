@@ -0,0 +1,1569 @@
+use crate::adjust::{self, Channels, Curve};
+use crate::edge::{self, EdgeParams};
+use crate::encode::{self, SaveOptions};
+use crate::filter::{self, Kernel};
+use crate::fractal::{self, Coloring, Family, FractalParams, Viewport};
+use crate::histogram;
+use crate::info;
+use crate::noise::{self, NoiseParams};
+use crate::op::{ArgKind, ArgSpec, Command, ImageOp, OpArgs};
+use crate::palette::{self, ColorStop, Palette};
+use crate::pattern::{self, PatternParams};
+use crate::registry::Registry;
+use crate::transform::{self, CropRect, ResizeMode, RotateOptions};
+use crate::{
+  open_image, parse_angle, save_animation, save_image, save_image_with, ImageUtilsError, Result,
+};
+use image::imageops::FilterType;
+use image::{DynamicImage, GenericImageView};
+use num_complex::Complex;
+use std::fs;
+use std::path::Path;
+
+/// Registers every built-in operation and command.
+///
+/// Arguments:
+///
+/// * `registry` - &mut Registry
+///
+/// Usage: ```ops::register_builtins(&mut registry);```
+pub fn register_builtins(registry: &mut Registry) {
+  registry.register_op(Blur);
+  registry.register_op(Brighten);
+  registry.register_op(Clahe);
+  registry.register_op(Contrast);
+  registry.register_op(Convolve);
+  registry.register_op(Crop);
+  registry.register_op(Curves);
+  registry.register_op(Edges);
+  registry.register_op(Equalize);
+  registry.register_op(Exposure);
+  registry.register_op(Flip);
+  registry.register_op(Gamma);
+  registry.register_op(Grayscale);
+  registry.register_op(Hue);
+  registry.register_op(Invert);
+  registry.register_op(Levels);
+  registry.register_op(Mirror);
+  registry.register_op(Resize);
+  registry.register_op(Rotate);
+  registry.register_op(Saturation);
+  registry.register_op(Scale);
+  registry.register_op(Sharpen);
+  registry.register_op(Thumbnail);
+  registry.register_op(Transpose);
+  registry.register_op(Transverse);
+  registry.register_command(Fractal);
+  registry.register_command(FractalZoom);
+  registry.register_command(Generate);
+  registry.register_command(Histogram);
+  registry.register_command(Info);
+  registry.register_command(Noise);
+}
+
+/// Blurs an image by an `amount`.
+///
+/// Usage: ```blur 2.5 input.png output.png```
+pub struct Blur;
+
+impl ImageOp for Blur {
+  fn name(&self) -> &'static str {
+    "blur"
+  }
+
+  fn args(&self) -> &[ArgSpec] {
+    const ARGS: &[ArgSpec] = &[ArgSpec::new("amount", ArgKind::F32)];
+    ARGS
+  }
+
+  fn help(&self) -> &'static str {
+    "Blurs an image with a gaussian of standard deviation <amount>."
+  }
+
+  fn apply(&self, img: &DynamicImage, args: &OpArgs) -> Result<DynamicImage> {
+    Ok(transform::blur(img, args.get("amount")?))
+  }
+}
+
+/// Brightens an image by an `amount`.
+///
+/// Usage: ```brighten 10 input.png output.png```
+pub struct Brighten;
+
+impl ImageOp for Brighten {
+  fn name(&self) -> &'static str {
+    "brighten"
+  }
+
+  fn args(&self) -> &[ArgSpec] {
+    const ARGS: &[ArgSpec] = &[ArgSpec::new("amount", ArgKind::I32)];
+    ARGS
+  }
+
+  fn help(&self) -> &'static str {
+    "Brightens an image by <amount>. Negative amounts darken it."
+  }
+
+  fn apply(&self, img: &DynamicImage, args: &OpArgs) -> Result<DynamicImage> {
+    Ok(transform::brighten(img, args.get("amount")?))
+  }
+}
+
+/// Equalizes the luminance of an image tile by tile with a clip limit.
+///
+/// Usage: ```clahe input.png output.png --tiles=8 --clip-limit=3```
+pub struct Clahe;
+
+impl ImageOp for Clahe {
+  fn name(&self) -> &'static str {
+    "clahe"
+  }
+
+  fn args(&self) -> &[ArgSpec] {
+    const ARGS: &[ArgSpec] = &[
+      ArgSpec::option("tiles", ArgKind::U32, "8"),
+      ArgSpec::option("clip-limit", ArgKind::F64, "2"),
+    ];
+    ARGS
+  }
+
+  fn help(&self) -> &'static str {
+    "Raises local contrast with contrast limited adaptive histogram equalization of the \
+     luminance over a <tiles> x <tiles> grid. <clip-limit> caps how much more common than \
+     average any tone can become; lower values give a subtler result. Colors are kept."
+  }
+
+  fn apply(&self, img: &DynamicImage, args: &OpArgs) -> Result<DynamicImage> {
+    let tiles: u32 = args.get("tiles")?;
+    let clip_limit: f64 = args.get("clip-limit")?;
+    args.check(&[
+      (
+        "tiles",
+        (1..=64).contains(&tiles),
+        "must be between 1 and 64",
+      ),
+      (
+        "clip-limit",
+        clip_limit >= 1.0 && clip_limit.is_finite(),
+        "must be 1 or more",
+      ),
+    ])?;
+
+    Ok(adjust::clahe(img, tiles, clip_limit))
+  }
+}
+
+/// Changes the contrast of an image by a percentage.
+///
+/// Usage: ```contrast 25 input.png output.png```
+pub struct Contrast;
+
+impl ImageOp for Contrast {
+  fn name(&self) -> &'static str {
+    "contrast"
+  }
+
+  fn args(&self) -> &[ArgSpec] {
+    const ARGS: &[ArgSpec] = &[ArgSpec::new("amount", ArgKind::F64)];
+    ARGS
+  }
+
+  fn help(&self) -> &'static str {
+    "Raises the contrast of an image by <amount> percent. Negative amounts lower it; -100 is flat gray."
+  }
+
+  fn apply(&self, img: &DynamicImage, args: &OpArgs) -> Result<DynamicImage> {
+    Ok(adjust::contrast(img, at_least_minus_100(args, "amount")?))
+  }
+}
+
+/// Convolves an image with a custom kernel.
+///
+/// Usage: ```convolve "0,-1,0;-1,5,-1;0,-1,0" input.png output.png --edge=mirror```
+pub struct Convolve;
+
+impl ImageOp for Convolve {
+  fn name(&self) -> &'static str {
+    "convolve"
+  }
+
+  fn args(&self) -> &[ArgSpec] {
+    const ARGS: &[ArgSpec] = &[
+      ArgSpec::new("kernel", ArgKind::Text),
+      ArgSpec::option("normalize", ArgKind::Bool, "true"),
+      ArgSpec::option("bias", ArgKind::F64, "0"),
+      ArgSpec::option("edge", ArgKind::Text, "clamp"),
+    ];
+    ARGS
+  }
+
+  fn help(&self) -> &'static str {
+    "Convolves an image with an odd-sized <kernel>, inline like \"1,2,1;2,4,2;1,2,1\" or the \
+     path of a text file with one row per line. The kernel is flipped, as in a true convolution. \
+     <normalize> scales the weights to add up to 1, <bias> (0 to 255) is added to every result \
+     and <edge> is clamp, wrap, mirror or zero."
+  }
+
+  fn apply(&self, img: &DynamicImage, args: &OpArgs) -> Result<DynamicImage> {
+    let subcommand = args.subcommand();
+    let spec: String = args.get("kernel")?;
+    let spec = if Path::new(&spec).is_file() {
+      fs::read_to_string(&spec).map_err(|source| ImageUtilsError::Io { path: spec, source })?
+    } else {
+      spec
+    };
+    let kernel: Kernel = spec
+      .parse()
+      .map_err(|reason| ImageUtilsError::bad_argument(subcommand, "kernel", reason))?;
+    let kernel = if args.get("normalize")? {
+      kernel.normalized()
+    } else {
+      kernel
+    };
+    let bias: f64 = args.get("bias")?;
+    if !bias.is_finite() {
+      return Err(ImageUtilsError::bad_argument(
+        subcommand,
+        "bias",
+        "must be a finite number",
+      ));
+    }
+    let edge = args
+      .get::<String>("edge")?
+      .parse()
+      .map_err(|reason| ImageUtilsError::bad_argument(subcommand, "edge", reason))?;
+
+    Ok(filter::convolve(img, &kernel, bias, edge))
+  }
+}
+
+/// Crops an image by `x`, `y`, `width` and `height`.
+///
+/// Usage: ```crop 0 0 640 480 input.png output.png```
+pub struct Crop;
+
+impl ImageOp for Crop {
+  fn name(&self) -> &'static str {
+    "crop"
+  }
+
+  fn args(&self) -> &[ArgSpec] {
+    const ARGS: &[ArgSpec] = &[
+      ArgSpec::new("x", ArgKind::U32),
+      ArgSpec::new("y", ArgKind::U32),
+      ArgSpec::new("width", ArgKind::U32),
+      ArgSpec::new("height", ArgKind::U32),
+    ];
+    ARGS
+  }
+
+  fn help(&self) -> &'static str {
+    "Crops a <width> x <height> rectangle out of an image, starting at (<x>, <y>)."
+  }
+
+  fn apply(&self, img: &DynamicImage, args: &OpArgs) -> Result<DynamicImage> {
+    let rect = CropRect {
+      x: args.get("x")?,
+      y: args.get("y")?,
+      width: args.get("width")?,
+      height: args.get("height")?,
+    };
+
+    transform::crop(img, rect)
+  }
+}
+
+/// Applies a tone curve to an image.
+///
+/// Usage: ```curves 0:0,64:48,192:210,255:255 input.png output.png --channel=red```
+pub struct Curves;
+
+impl ImageOp for Curves {
+  fn name(&self) -> &'static str {
+    "curves"
+  }
+
+  fn args(&self) -> &[ArgSpec] {
+    const ARGS: &[ArgSpec] = &[
+      ArgSpec::new("points", ArgKind::Text),
+      ArgSpec::option("channel", ArgKind::Text, "all"),
+    ];
+    ARGS
+  }
+
+  fn help(&self) -> &'static str {
+    "Maps tones through a smooth curve through <points> like 0:0,64:48,192:210,255:255, on all \
+     channels or just the red, green or blue <channel>."
+  }
+
+  fn apply(&self, img: &DynamicImage, args: &OpArgs) -> Result<DynamicImage> {
+    let curve: Curve = args
+      .get::<String>("points")?
+      .parse()
+      .map_err(|reason| ImageUtilsError::bad_argument(args.subcommand(), "points", reason))?;
+
+    Ok(adjust::curves(img, &curve, channels(args)?))
+  }
+}
+
+/// Finds the edges in an image with an `operator`.
+///
+/// Usage: ```edges canny input.png output.png --low=20 --high=50```
+pub struct Edges;
+
+impl ImageOp for Edges {
+  fn name(&self) -> &'static str {
+    "edges"
+  }
+
+  fn args(&self) -> &[ArgSpec] {
+    const ARGS: &[ArgSpec] = &[
+      ArgSpec::new("operator", ArgKind::Text),
+      ArgSpec::option("sigma", ArgKind::F64, "1.4"),
+      ArgSpec::option("threshold", ArgKind::F64, "0"),
+      ArgSpec::option("low", ArgKind::F64, "20"),
+      ArgSpec::option("high", ArgKind::F64, "50"),
+    ];
+    ARGS
+  }
+
+  fn help(&self) -> &'static str {
+    "Maps the edges of an image's luminance with an <operator>: sobel, prewitt, log (Laplacian \
+     of Gaussian) or canny. log and canny blur with <sigma> first. A <threshold> above 0 \
+     (0 to 255) turns sobel, prewitt and log into black and white maps. canny keeps edges \
+     stronger than <high> and the weaker ones above <low> connected to them."
+  }
+
+  fn apply(&self, img: &DynamicImage, args: &OpArgs) -> Result<DynamicImage> {
+    let subcommand = args.subcommand();
+    let operator = args
+      .get::<String>("operator")?
+      .parse()
+      .map_err(|reason| ImageUtilsError::bad_argument(subcommand, "operator", reason))?;
+    let params = EdgeParams {
+      operator,
+      sigma: args.get("sigma")?,
+      threshold: args.get("threshold")?,
+      low: args.get("low")?,
+      high: args.get("high")?,
+    };
+    let in_range = |value: f64| (0.0..=255.0).contains(&value);
+    args.check(&[
+      (
+        "sigma",
+        (0.0..=100.0).contains(&params.sigma),
+        "must be between 0 and 100",
+      ),
+      (
+        "threshold",
+        in_range(params.threshold),
+        "must be between 0 and 255",
+      ),
+      ("low", in_range(params.low), "must be between 0 and 255"),
+      (
+        "high",
+        in_range(params.high) && params.high >= params.low,
+        "must be between low and 255",
+      ),
+    ])?;
+
+    Ok(edge::detect(img, params))
+  }
+}
+
+/// Equalizes the luminance histogram of an image.
+///
+/// Usage: ```equalize input.png output.png```
+pub struct Equalize;
+
+impl ImageOp for Equalize {
+  fn name(&self) -> &'static str {
+    "equalize"
+  }
+
+  fn args(&self) -> &[ArgSpec] {
+    &[]
+  }
+
+  fn help(&self) -> &'static str {
+    "Spreads the luminance of an image evenly over the whole tonal range. Colors are kept."
+  }
+
+  fn apply(&self, img: &DynamicImage, _args: &OpArgs) -> Result<DynamicImage> {
+    Ok(adjust::equalize(img))
+  }
+}
+
+/// Changes the exposure of an image by a number of stops.
+///
+/// Usage: ```exposure -1.5 input.png output.png```
+pub struct Exposure;
+
+impl ImageOp for Exposure {
+  fn name(&self) -> &'static str {
+    "exposure"
+  }
+
+  fn args(&self) -> &[ArgSpec] {
+    const ARGS: &[ArgSpec] = &[ArgSpec::new("stops", ArgKind::F64)];
+    ARGS
+  }
+
+  fn help(&self) -> &'static str {
+    "Changes the exposure of an image by <stops>. Every stop doubles the light; negative stops halve it."
+  }
+
+  fn apply(&self, img: &DynamicImage, args: &OpArgs) -> Result<DynamicImage> {
+    let stops: f64 = args.get("stops")?;
+    if !stops.is_finite() {
+      return Err(ImageUtilsError::bad_argument(
+        args.subcommand(),
+        "stops",
+        "must be a finite number",
+      ));
+    }
+
+    Ok(adjust::exposure(img, stops))
+  }
+}
+
+/// Flips an image horizontally or vertically.
+///
+/// Usage: ```flip horizontal input.png output.png```
+pub struct Flip;
+
+impl ImageOp for Flip {
+  fn name(&self) -> &'static str {
+    "flip"
+  }
+
+  fn args(&self) -> &[ArgSpec] {
+    const ARGS: &[ArgSpec] = &[ArgSpec::new("axis", ArgKind::Text)];
+    ARGS
+  }
+
+  fn help(&self) -> &'static str {
+    "Flips an image along an <axis>: horizontal (h) or vertical (v)."
+  }
+
+  fn apply(&self, img: &DynamicImage, args: &OpArgs) -> Result<DynamicImage> {
+    let axis = args
+      .get::<String>("axis")?
+      .parse()
+      .map_err(|reason| ImageUtilsError::bad_argument(args.subcommand(), "axis", reason))?;
+
+    Ok(transform::flip(img, axis))
+  }
+}
+
+/// Gamma corrects an image.
+///
+/// Usage: ```gamma 2.2 input.png output.png```
+pub struct Gamma;
+
+impl ImageOp for Gamma {
+  fn name(&self) -> &'static str {
+    "gamma"
+  }
+
+  fn args(&self) -> &[ArgSpec] {
+    const ARGS: &[ArgSpec] = &[ArgSpec::new("value", ArgKind::F64)];
+    ARGS
+  }
+
+  fn help(&self) -> &'static str {
+    "Gamma corrects an image. A <value> above 1 brightens the midtones and below 1 darkens them."
+  }
+
+  fn apply(&self, img: &DynamicImage, args: &OpArgs) -> Result<DynamicImage> {
+    let gamma: f64 = args.get("value")?;
+    if !(gamma > 0.0 && gamma.is_finite()) {
+      return Err(ImageUtilsError::bad_argument(
+        args.subcommand(),
+        "value",
+        "must be greater than 0",
+      ));
+    }
+
+    Ok(adjust::gamma(img, gamma))
+  }
+}
+
+/// Grayscales an image.
+///
+/// Usage: ```grayscale input.png output.png```
+pub struct Grayscale;
+
+impl ImageOp for Grayscale {
+  fn name(&self) -> &'static str {
+    "grayscale"
+  }
+
+  fn args(&self) -> &[ArgSpec] {
+    &[]
+  }
+
+  fn help(&self) -> &'static str {
+    "Converts an image to grayscale."
+  }
+
+  fn apply(&self, img: &DynamicImage, _args: &OpArgs) -> Result<DynamicImage> {
+    Ok(transform::grayscale(img))
+  }
+}
+
+/// Rotates the hue of an image.
+///
+/// Usage: ```hue 120 input.png output.png```
+pub struct Hue;
+
+impl ImageOp for Hue {
+  fn name(&self) -> &'static str {
+    "hue"
+  }
+
+  fn args(&self) -> &[ArgSpec] {
+    const ARGS: &[ArgSpec] = &[ArgSpec::new("degrees", ArgKind::F64)];
+    ARGS
+  }
+
+  fn help(&self) -> &'static str {
+    "Rotates the hue of every color in an image by <degrees> around the color wheel."
+  }
+
+  fn apply(&self, img: &DynamicImage, args: &OpArgs) -> Result<DynamicImage> {
+    let degrees = parse_angle(args.subcommand(), "degrees", args.get("degrees")?)?;
+
+    Ok(adjust::hue(img, degrees))
+  }
+}
+
+/// Inverts an image.
+///
+/// Usage: ```invert input.png output.png```
+pub struct Invert;
+
+impl ImageOp for Invert {
+  fn name(&self) -> &'static str {
+    "invert"
+  }
+
+  fn args(&self) -> &[ArgSpec] {
+    &[]
+  }
+
+  fn help(&self) -> &'static str {
+    "Inverts the colors of an image."
+  }
+
+  fn apply(&self, img: &DynamicImage, _args: &OpArgs) -> Result<DynamicImage> {
+    Ok(transform::invert(img))
+  }
+}
+
+/// Adjusts the black point, white point and midtones of an image.
+///
+/// Usage: ```levels input.png output.png --black=20 --white=235 --gamma=1.2```
+pub struct Levels;
+
+impl ImageOp for Levels {
+  fn name(&self) -> &'static str {
+    "levels"
+  }
+
+  fn args(&self) -> &[ArgSpec] {
+    const ARGS: &[ArgSpec] = &[
+      ArgSpec::option("black", ArgKind::F64, "0"),
+      ArgSpec::option("white", ArgKind::F64, "255"),
+      ArgSpec::option("gamma", ArgKind::F64, "1"),
+      ArgSpec::option("out-black", ArgKind::F64, "0"),
+      ArgSpec::option("out-white", ArgKind::F64, "255"),
+      ArgSpec::option("channel", ArgKind::Text, "all"),
+    ];
+    ARGS
+  }
+
+  fn help(&self) -> &'static str {
+    "Stretches the tones between the input <black> and <white> points (0 to 255) to the output \
+     range, bending the midtones by <gamma>, on all channels or just the red, green or blue \
+     <channel>."
+  }
+
+  fn apply(&self, img: &DynamicImage, args: &OpArgs) -> Result<DynamicImage> {
+    let subcommand = args.subcommand();
+    let mut values = [0.0; 4];
+    for (value, name) in values
+      .iter_mut()
+      .zip(["black", "white", "out-black", "out-white"].iter())
+    {
+      let level: f64 = args.get(name)?;
+      if !(0.0..=255.0).contains(&level) {
+        return Err(ImageUtilsError::bad_argument(
+          subcommand,
+          name,
+          "must be between 0 and 255",
+        ));
+      }
+      *value = level / 255.0;
+    }
+    let [black, white, out_black, out_white] = values;
+    let levels = adjust::Levels {
+      black,
+      white,
+      gamma: args.get("gamma")?,
+      out_black,
+      out_white,
+    };
+    if levels.black >= levels.white {
+      return Err(ImageUtilsError::bad_argument(
+        subcommand,
+        "white",
+        "must be greater than black",
+      ));
+    }
+    if !(levels.gamma > 0.0 && levels.gamma.is_finite()) {
+      return Err(ImageUtilsError::bad_argument(
+        subcommand,
+        "gamma",
+        "must be greater than 0",
+      ));
+    }
+
+    Ok(adjust::levels(img, levels, channels(args)?))
+  }
+}
+
+/// Reflects half or a quadrant of an image over the rest of it.
+///
+/// Usage: ```mirror top-left input.png output.png```
+pub struct Mirror;
+
+impl ImageOp for Mirror {
+  fn name(&self) -> &'static str {
+    "mirror"
+  }
+
+  fn args(&self) -> &[ArgSpec] {
+    const ARGS: &[ArgSpec] = &[ArgSpec::new("region", ArgKind::Text)];
+    ARGS
+  }
+
+  fn help(&self) -> &'static str {
+    "Reflects a <region> of an image over the rest of it: left, right, top or bottom half, or \
+     the top-left, top-right, bottom-left or bottom-right quadrant."
+  }
+
+  fn apply(&self, img: &DynamicImage, args: &OpArgs) -> Result<DynamicImage> {
+    let region = args
+      .get::<String>("region")?
+      .parse()
+      .map_err(|reason| ImageUtilsError::bad_argument(args.subcommand(), "region", reason))?;
+
+    Ok(transform::mirror(img, region))
+  }
+}
+
+/// Resizes an image to a new width and height.
+///
+/// Usage: ```resize 640 480 input.png output.png --mode=fit --filter=catmull-rom```
+pub struct Resize;
+
+impl ImageOp for Resize {
+  fn name(&self) -> &'static str {
+    "resize"
+  }
+
+  fn args(&self) -> &[ArgSpec] {
+    const ARGS: &[ArgSpec] = &[
+      ArgSpec::new("width", ArgKind::U32),
+      ArgSpec::new("height", ArgKind::U32),
+      ArgSpec::option("mode", ArgKind::Text, "exact"),
+      ArgSpec::option("filter", ArgKind::Text, "lanczos3"),
+    ];
+    ARGS
+  }
+
+  fn help(&self) -> &'static str {
+    "Resizes an image to <width> x <height>. <mode> exact stretches to the size, and a <width> \
+     or <height> of 0 keeps the aspect ratio; fit shrinks or grows to fit inside it; fill \
+     covers it and crops the overhang. <filter> is nearest, triangle, catmull-rom, gaussian or \
+     lanczos3."
+  }
+
+  fn apply(&self, img: &DynamicImage, args: &OpArgs) -> Result<DynamicImage> {
+    let subcommand = args.subcommand();
+    let (width, height): (u32, u32) = (args.get("width")?, args.get("height")?);
+    let mode = args
+      .get::<String>("mode")?
+      .parse()
+      .map_err(|reason| ImageUtilsError::bad_argument(subcommand, "mode", reason))?;
+    if width == 0 && (height == 0 || mode != ResizeMode::Exact) {
+      return Err(ImageUtilsError::bad_argument(
+        subcommand,
+        "width",
+        "must be greater than 0",
+      ));
+    }
+    if height == 0 && mode != ResizeMode::Exact {
+      return Err(ImageUtilsError::bad_argument(
+        subcommand,
+        "height",
+        "must be greater than 0",
+      ));
+    }
+
+    Ok(transform::resize(img, width, height, mode, filter(args)?))
+  }
+}
+
+/// Rotates an image clockwise by any angle.
+///
+/// Usage: ```rotate 30 input.png output.png --sampling=bicubic --expand=false```
+pub struct Rotate;
+
+impl ImageOp for Rotate {
+  fn name(&self) -> &'static str {
+    "rotate"
+  }
+
+  fn args(&self) -> &[ArgSpec] {
+    const ARGS: &[ArgSpec] = &[
+      ArgSpec::new("amount", ArgKind::F64),
+      ArgSpec::option("sampling", ArgKind::Text, "bilinear"),
+      ArgSpec::option("expand", ArgKind::Bool, "true"),
+      ArgSpec::option("fill", ArgKind::Text, "000000"),
+    ];
+    ARGS
+  }
+
+  fn help(&self) -> &'static str {
+    "Rotates an image clockwise by <amount> degrees. Quarter turns are lossless; other angles \
+     are resampled with nearest, bilinear or bicubic <sampling>. <expand> grows the canvas to \
+     fit instead of cropping, and uncovered areas are <fill>ed with a hex color or transparent."
+  }
+
+  fn apply(&self, img: &DynamicImage, args: &OpArgs) -> Result<DynamicImage> {
+    let subcommand = args.subcommand();
+    let degrees = parse_angle(subcommand, "amount", args.get("amount")?)?;
+    let fill = match args.get::<String>("fill")?.as_str() {
+      "transparent" => [0, 0, 0, 0],
+      hex => {
+        let [red, green, blue] = palette::parse_color(hex).ok_or_else(|| {
+          ImageUtilsError::bad_argument(
+            subcommand,
+            "fill",
+            "must be a hex color like ff8800 or transparent",
+          )
+        })?;
+        [red, green, blue, 255]
+      }
+    };
+    let options = RotateOptions {
+      sampling: args
+        .get::<String>("sampling")?
+        .parse()
+        .map_err(|reason| ImageUtilsError::bad_argument(subcommand, "sampling", reason))?,
+      expand: args.get("expand")?,
+      fill,
+    };
+
+    Ok(transform::rotate_by(img, degrees, options))
+  }
+}
+
+/// Changes the saturation of an image by a percentage.
+///
+/// Usage: ```saturation -50 input.png output.png```
+pub struct Saturation;
+
+impl ImageOp for Saturation {
+  fn name(&self) -> &'static str {
+    "saturation"
+  }
+
+  fn args(&self) -> &[ArgSpec] {
+    const ARGS: &[ArgSpec] = &[ArgSpec::new("amount", ArgKind::F64)];
+    ARGS
+  }
+
+  fn help(&self) -> &'static str {
+    "Raises the saturation of an image by <amount> percent. Negative amounts lower it; -100 is grayscale."
+  }
+
+  fn apply(&self, img: &DynamicImage, args: &OpArgs) -> Result<DynamicImage> {
+    Ok(adjust::saturation(img, at_least_minus_100(args, "amount")?))
+  }
+}
+
+/// Scales an image by a percentage.
+///
+/// Usage: ```scale 50 input.png output.png```
+pub struct Scale;
+
+impl ImageOp for Scale {
+  fn name(&self) -> &'static str {
+    "scale"
+  }
+
+  fn args(&self) -> &[ArgSpec] {
+    const ARGS: &[ArgSpec] = &[
+      ArgSpec::new("percent", ArgKind::F64),
+      ArgSpec::option("filter", ArgKind::Text, "lanczos3"),
+    ];
+    ARGS
+  }
+
+  fn help(&self) -> &'static str {
+    "Scales both sides of an image by <percent>, keeping its aspect ratio."
+  }
+
+  fn apply(&self, img: &DynamicImage, args: &OpArgs) -> Result<DynamicImage> {
+    let percent: f64 = args.get("percent")?;
+    if !(percent > 0.0 && percent.is_finite()) {
+      return Err(ImageUtilsError::bad_argument(
+        args.subcommand(),
+        "percent",
+        "must be greater than 0",
+      ));
+    }
+
+    Ok(transform::scale(img, percent, filter(args)?))
+  }
+}
+
+/// Sharpens an image with an unsharp mask.
+///
+/// Usage: ```sharpen 0.8 input.png output.png --radius=1.5 --threshold=2```
+pub struct Sharpen;
+
+impl ImageOp for Sharpen {
+  fn name(&self) -> &'static str {
+    "sharpen"
+  }
+
+  fn args(&self) -> &[ArgSpec] {
+    const ARGS: &[ArgSpec] = &[
+      ArgSpec::new("amount", ArgKind::F64),
+      ArgSpec::option("radius", ArgKind::F32, "1"),
+      ArgSpec::option("threshold", ArgKind::F64, "0"),
+    ];
+    ARGS
+  }
+
+  fn help(&self) -> &'static str {
+    "Sharpens an image with an unsharp mask, adding back <amount> times the detail a gaussian \
+     blur of <radius> removes. Differences under <threshold> (0 to 255) are left alone."
+  }
+
+  fn apply(&self, img: &DynamicImage, args: &OpArgs) -> Result<DynamicImage> {
+    let amount: f64 = args.get("amount")?;
+    let radius: f32 = args.get("radius")?;
+    let threshold: f64 = args.get("threshold")?;
+    args.check(&[
+      (
+        "amount",
+        amount >= 0.0 && amount.is_finite(),
+        "must be 0 or more",
+      ),
+      (
+        "radius",
+        radius > 0.0 && radius.is_finite(),
+        "must be greater than 0",
+      ),
+      (
+        "threshold",
+        (0.0..=255.0).contains(&threshold),
+        "must be between 0 and 255",
+      ),
+    ])?;
+
+    Ok(filter::sharpen(img, radius, amount, threshold))
+  }
+}
+
+/// Shrinks an image to fit inside a square.
+///
+/// Usage: ```thumbnail 128 input.png output.png```
+pub struct Thumbnail;
+
+impl ImageOp for Thumbnail {
+  fn name(&self) -> &'static str {
+    "thumbnail"
+  }
+
+  fn args(&self) -> &[ArgSpec] {
+    const ARGS: &[ArgSpec] = &[ArgSpec::new("size", ArgKind::U32)];
+    ARGS
+  }
+
+  fn help(&self) -> &'static str {
+    "Quickly shrinks an image to fit inside a <size> x <size> square, keeping its aspect ratio."
+  }
+
+  fn apply(&self, img: &DynamicImage, args: &OpArgs) -> Result<DynamicImage> {
+    let size = args.get("size")?;
+    if size == 0 {
+      return Err(ImageUtilsError::bad_argument(
+        args.subcommand(),
+        "size",
+        "must be greater than 0",
+      ));
+    }
+
+    Ok(transform::thumbnail(img, size))
+  }
+}
+
+/// Transposes an image.
+///
+/// Usage: ```transpose input.png output.png```
+pub struct Transpose;
+
+impl ImageOp for Transpose {
+  fn name(&self) -> &'static str {
+    "transpose"
+  }
+
+  fn args(&self) -> &[ArgSpec] {
+    &[]
+  }
+
+  fn help(&self) -> &'static str {
+    "Reflects an image across its top-left to bottom-right diagonal."
+  }
+
+  fn apply(&self, img: &DynamicImage, _args: &OpArgs) -> Result<DynamicImage> {
+    Ok(transform::transpose(img))
+  }
+}
+
+/// Transverses an image.
+///
+/// Usage: ```transverse input.png output.png```
+pub struct Transverse;
+
+impl ImageOp for Transverse {
+  fn name(&self) -> &'static str {
+    "transverse"
+  }
+
+  fn args(&self) -> &[ArgSpec] {
+    &[]
+  }
+
+  fn help(&self) -> &'static str {
+    "Reflects an image across its top-right to bottom-left diagonal."
+  }
+
+  fn apply(&self, img: &DynamicImage, _args: &OpArgs) -> Result<DynamicImage> {
+    Ok(transform::transverse(img))
+  }
+}
+
+/// Parses a percentage argument called `name` that can't take away more than everything.
+fn at_least_minus_100(args: &OpArgs, name: &str) -> Result<f64> {
+  let amount: f64 = args.get(name)?;
+  if !(amount >= -100.0 && amount.is_finite()) {
+    return Err(ImageUtilsError::bad_argument(
+      args.subcommand(),
+      name,
+      "must be -100 or more",
+    ));
+  }
+
+  Ok(amount)
+}
+
+/// Parses the `--channel` option shared by the tonal adjustments.
+fn channels(args: &OpArgs) -> Result<Channels> {
+  args
+    .get::<String>("channel")?
+    .parse()
+    .map_err(|reason| ImageUtilsError::bad_argument(args.subcommand(), "channel", reason))
+}
+
+/// Parses the `--filter` option shared by the resizing operations.
+fn filter(args: &OpArgs) -> Result<FilterType> {
+  transform::parse_filter(&args.get::<String>("filter")?)
+    .map_err(|reason| ImageUtilsError::bad_argument(args.subcommand(), "filter", reason))
+}
+
+/// Generates a fractal image.
+///
+/// Usage: ```fractal output.png --zoom=4 --center-re=0.3```
+pub struct Fractal;
+
+impl Command for Fractal {
+  fn name(&self) -> &'static str {
+    "fractal"
+  }
+
+  fn args(&self) -> &[ArgSpec] {
+    const ARGS: &[ArgSpec] = &[
+      ArgSpec::new("output", ArgKind::Text),
+      ArgSpec::option("family", ArgKind::Text, "julia"),
+      ArgSpec::option("power", ArgKind::U32, "3"),
+      ArgSpec::option("width", ArgKind::U32, "800"),
+      ArgSpec::option("height", ArgKind::U32, "800"),
+      ArgSpec::option("c-re", ArgKind::F64, "-0.4"),
+      ArgSpec::option("c-im", ArgKind::F64, "0.6"),
+      ArgSpec::option("center-re", ArgKind::F64, "0"),
+      ArgSpec::option("center-im", ArgKind::F64, "0"),
+      ArgSpec::option("zoom", ArgKind::F64, "1"),
+      ArgSpec::option("iterations", ArgKind::U32, "255"),
+      ArgSpec::option("escape-radius", ArgKind::F64, "2"),
+      ArgSpec::option("palette", ArgKind::Text, "classic"),
+      ArgSpec::option("smooth", ArgKind::Bool, "false"),
+      ArgSpec::option("samples", ArgKind::U32, "1"),
+      ArgSpec::option("threads", ArgKind::U32, "0"),
+    ];
+    ARGS
+  }
+
+  fn saves_image(&self) -> bool {
+    true
+  }
+
+  fn help(&self) -> &'static str {
+    "Renders a julia, mandelbrot, burning-ship, tricorn or multibrot (z^<power> + c) fractal. \
+     <palette> is classic, a built-in (fire, grayscale, ocean, rainbow, twilight) or stops like \
+     0:000000,1:ffffff. <samples> averages a <samples> x <samples> grid per pixel to smooth edges."
+  }
+
+  fn run(&self, args: &OpArgs) -> Result<()> {
+    save_image_with(
+      fractal::render(&fractal_params(args)?, args.get("threads")?),
+      args.get("output")?,
+      &save_options(args)?,
+    )
+  }
+}
+
+/// Renders an animated GIF zooming into a fractal.
+///
+/// Usage: ```fractal-zoom output.gif --family=mandelbrot --end-re=-0.743 --end-im=0.131 --end-zoom=200```
+pub struct FractalZoom;
+
+impl Command for FractalZoom {
+  fn name(&self) -> &'static str {
+    "fractal-zoom"
+  }
+
+  fn args(&self) -> &[ArgSpec] {
+    const ARGS: &[ArgSpec] = &[
+      ArgSpec::new("output", ArgKind::Text),
+      ArgSpec::option("family", ArgKind::Text, "julia"),
+      ArgSpec::option("power", ArgKind::U32, "3"),
+      ArgSpec::option("width", ArgKind::U32, "400"),
+      ArgSpec::option("height", ArgKind::U32, "400"),
+      ArgSpec::option("c-re", ArgKind::F64, "-0.4"),
+      ArgSpec::option("c-im", ArgKind::F64, "0.6"),
+      ArgSpec::option("center-re", ArgKind::F64, "0"),
+      ArgSpec::option("center-im", ArgKind::F64, "0"),
+      ArgSpec::option("zoom", ArgKind::F64, "1"),
+      ArgSpec::option("end-re", ArgKind::F64, "0"),
+      ArgSpec::option("end-im", ArgKind::F64, "0"),
+      ArgSpec::option("end-zoom", ArgKind::F64, "10"),
+      ArgSpec::option("frames", ArgKind::U32, "30"),
+      ArgSpec::option("delay", ArgKind::U32, "100"),
+      ArgSpec::option("iterations", ArgKind::U32, "255"),
+      ArgSpec::option("escape-radius", ArgKind::F64, "2"),
+      ArgSpec::option("palette", ArgKind::Text, "classic"),
+      ArgSpec::option("smooth", ArgKind::Bool, "false"),
+      ArgSpec::option("samples", ArgKind::U32, "1"),
+      ArgSpec::option("threads", ArgKind::U32, "0"),
+    ];
+    ARGS
+  }
+
+  fn help(&self) -> &'static str {
+    "Renders <frames> fractal frames zooming from the center and zoom to the end center and \
+     end zoom, shown for <delay> milliseconds each, as an animated GIF. Takes the same options \
+     as fractal."
+  }
+
+  fn run(&self, args: &OpArgs) -> Result<()> {
+    let params = fractal_params(args)?;
+    let end = Viewport {
+      center: Complex::new(args.get("end-re")?, args.get("end-im")?),
+      zoom: args.get("end-zoom")?,
+    };
+    let frames: u32 = args.get("frames")?;
+    args.check(&[
+      (
+        "end-zoom",
+        end.zoom > 0.0 && end.zoom.is_finite(),
+        "must be a finite number greater than 0",
+      ),
+      (
+        "end-re",
+        end.center.re.is_finite(),
+        "must be a finite number",
+      ),
+      (
+        "end-im",
+        end.center.im.is_finite(),
+        "must be a finite number",
+      ),
+      ("frames", frames > 0, "must be greater than 0"),
+    ])?;
+
+    save_animation(
+      fractal::render_zoom(&params, &end, frames, args.get("threads")?),
+      args.get("delay")?,
+      args.get("output")?,
+    )
+  }
+}
+
+/// Builds and validates `FractalParams` from the `fractal` options.
+///
+/// Arguments:
+///
+/// * `args` - &OpArgs
+///
+/// Usage: ```fractal_params(args)?;```
+pub fn fractal_params(args: &OpArgs) -> Result<FractalParams> {
+  let family = match args.get::<String>("family")?.parse::<Family>() {
+    Ok(Family::Multibrot(_)) => Family::Multibrot(args.get("power")?),
+    Ok(family) => family,
+    Err(reason) => {
+      return Err(ImageUtilsError::bad_argument(
+        args.subcommand(),
+        "family",
+        reason,
+      ))
+    }
+  };
+
+  let params = FractalParams {
+    family,
+    width: args.get("width")?,
+    height: args.get("height")?,
+    constant: Complex::new(args.get("c-re")?, args.get("c-im")?),
+    viewport: Viewport {
+      center: Complex::new(args.get("center-re")?, args.get("center-im")?),
+      zoom: args.get("zoom")?,
+    },
+    max_iterations: args.get("iterations")?,
+    escape_radius: args.get("escape-radius")?,
+    coloring: match args.get::<String>("palette")?.as_str() {
+      "classic" => Coloring::Classic,
+      spec => Coloring::Palette {
+        palette: spec
+          .parse::<Palette>()
+          .map_err(|reason| ImageUtilsError::bad_argument(args.subcommand(), "palette", reason))?,
+        smooth: args.get("smooth")?,
+      },
+    },
+    samples: args.get("samples")?,
+  };
+
+  let greater_than_zero = "must be greater than 0";
+  let finite = "must be a finite number";
+  args.check(&[
+    ("width", params.width > 0, greater_than_zero),
+    ("height", params.height > 0, greater_than_zero),
+    (
+      "zoom",
+      params.viewport.zoom > 0.0 && params.viewport.zoom.is_finite(),
+      "must be a finite number greater than 0",
+    ),
+    ("c-re", params.constant.re.is_finite(), finite),
+    ("c-im", params.constant.im.is_finite(), finite),
+    ("center-re", params.viewport.center.re.is_finite(), finite),
+    ("center-im", params.viewport.center.im.is_finite(), finite),
+    ("iterations", params.max_iterations > 0, greater_than_zero),
+    (
+      "samples",
+      (1..=16).contains(&params.samples),
+      "must be between 1 and 16",
+    ),
+    (
+      "escape-radius",
+      params.escape_radius > 1.0 && params.escape_radius.is_finite(),
+      "must be a finite number greater than 1",
+    ),
+    (
+      "power",
+      !matches!(family, Family::Multibrot(power) if power < 2),
+      "must be at least 2",
+    ),
+  ])?;
+
+  Ok(params)
+}
+
+/// Generates a solid color, gradient, checkerboard, stripes or grid image.
+///
+/// Usage: ```generate 640 480 0 0 0 output.png --pattern=checkerboard --size=16```
+pub struct Generate;
+
+impl Command for Generate {
+  fn name(&self) -> &'static str {
+    "generate"
+  }
+
+  fn args(&self) -> &[ArgSpec] {
+    const ARGS: &[ArgSpec] = &[
+      ArgSpec::new("width", ArgKind::U32),
+      ArgSpec::new("height", ArgKind::U32),
+      ArgSpec::optional("red", ArgKind::U8),
+      ArgSpec::optional("green", ArgKind::U8),
+      ArgSpec::optional("blue", ArgKind::U8),
+      ArgSpec::new("output", ArgKind::Text),
+      ArgSpec::option("pattern", ArgKind::Text, "solid"),
+      ArgSpec::option("to", ArgKind::Text, "ffffff"),
+      ArgSpec::option("palette", ArgKind::Text, "none"),
+      ArgSpec::option("angle", ArgKind::F64, "0"),
+      ArgSpec::option("size", ArgKind::U32, "32"),
+      ArgSpec::option("line-width", ArgKind::U32, "1"),
+      ArgSpec::option("threads", ArgKind::U32, "0"),
+    ];
+    ARGS
+  }
+
+  fn saves_image(&self) -> bool {
+    true
+  }
+
+  fn help(&self) -> &'static str {
+    "Generates a <width> x <height> image. <pattern> is solid, linear, radial, checkerboard, \
+     stripes or grid, drawn from the <red> <green> <blue> color to the <to> hex color, or \
+     through a <palette> like fractal's, which takes the place of the color. Linear gradients \
+     and stripes run at <angle> degrees."
+  }
+
+  fn run(&self, args: &OpArgs) -> Result<()> {
+    save_image_with(
+      pattern::render(&pattern_params(args)?, args.get("threads")?),
+      args.get("output")?,
+      &save_options(args)?,
+    )
+  }
+}
+
+/// Builds and validates `PatternParams` from the `generate` arguments.
+///
+/// Arguments:
+///
+/// * `args` - &OpArgs
+///
+/// Usage: ```pattern_params(args)?;```
+pub fn pattern_params(args: &OpArgs) -> Result<PatternParams> {
+  let subcommand = args.subcommand();
+  let bad_argument =
+    |argument: &str, reason: String| ImageUtilsError::bad_argument(subcommand, argument, reason);
+
+  let has_color = args.has("red");
+  let palette = match args.get::<String>("palette")?.as_str() {
+    "none" if !has_color => {
+      return Err(bad_argument(
+        "red",
+        "is required, along with <green> and <blue>, unless a palette is given".to_string(),
+      ))
+    }
+    "none" => {
+      let from = [args.get("red")?, args.get("green")?, args.get("blue")?];
+      let to = palette::parse_color(&args.get::<String>("to")?)
+        .ok_or_else(|| bad_argument("to", "must be a hex color like ff8800".to_string()))?;
+
+      Palette::new(vec![
+        ColorStop {
+          position: 0.0,
+          color: from,
+        },
+        ColorStop {
+          position: 1.0,
+          color: to,
+        },
+      ])
+      .map_err(|reason| bad_argument("to", reason))?
+    }
+    _ if has_color => {
+      return Err(bad_argument(
+        "palette",
+        "replaces <red> <green> <blue>, so they can't be given with it".to_string(),
+      ))
+    }
+    spec => spec
+      .parse::<Palette>()
+      .map_err(|reason| bad_argument("palette", reason))?,
+  };
+
+  let params = PatternParams {
+    pattern: args
+      .get::<String>("pattern")?
+      .parse()
+      .map_err(|reason| bad_argument("pattern", reason))?,
+    width: args.get("width")?,
+    height: args.get("height")?,
+    palette,
+    angle: parse_angle(subcommand, "angle", args.get("angle")?)?,
+    size: args.get("size")?,
+    line_width: args.get("line-width")?,
+  };
+
+  let greater_than_zero = "must be greater than 0";
+  args.check(&[
+    ("width", params.width > 0, greater_than_zero),
+    ("height", params.height > 0, greater_than_zero),
+    ("size", params.size > 0, greater_than_zero),
+    ("line-width", params.line_width > 0, greater_than_zero),
+  ])?;
+
+  Ok(params)
+}
+
+/// Exports the histograms and statistics of an image's channels as data.
+///
+/// Usage: ```histogram input.png stats.json --export=json --bins=64 --chart=chart.png```
+pub struct Histogram;
+
+impl Command for Histogram {
+  fn name(&self) -> &'static str {
+    "histogram"
+  }
+
+  fn args(&self) -> &[ArgSpec] {
+    const ARGS: &[ArgSpec] = &[
+      ArgSpec::new("input", ArgKind::Text),
+      ArgSpec::new("output", ArgKind::Text),
+      ArgSpec::option("export", ArgKind::Text, "csv"),
+      ArgSpec::option("bins", ArgKind::U32, "256"),
+      ArgSpec::option("chart", ArgKind::Text, "none"),
+    ];
+    ARGS
+  }
+
+  fn help(&self) -> &'static str {
+    "Writes the red, green, blue, alpha and luminance histograms of an image to <output> (- for \
+     the terminal) as csv or json <export> data, with <bins> counts and the pixel count, mean, \
+     median, standard deviation, min and max of each channel on a 0 to 255 scale. <chart> also \
+     draws them to an image."
+  }
+
+  fn run(&self, args: &OpArgs) -> Result<()> {
+    let subcommand = args.subcommand();
+    let format: histogram::Format = args
+      .get::<String>("export")?
+      .parse()
+      .map_err(|reason| ImageUtilsError::bad_argument(subcommand, "export", reason))?;
+    let bins: u32 = args.get("bins")?;
+    if !(1..=65536).contains(&bins) {
+      return Err(ImageUtilsError::bad_argument(
+        subcommand,
+        "bins",
+        "must be between 1 and 65536",
+      ));
+    }
+
+    let img = open_image(args.get("input")?)?;
+    let histograms = histogram::compute(&img);
+    let data = histogram::export(
+      &histograms,
+      img.width(),
+      img.height(),
+      bins as usize,
+      format,
+    );
+    let output: String = args.get("output")?;
+    if output == "-" {
+      print!("{}", data);
+    } else {
+      fs::write(&output, data).map_err(|source| ImageUtilsError::Io {
+        path: output,
+        source,
+      })?;
+    }
+
+    let chart: String = args.get("chart")?;
+    if chart != "none" {
+      save_image(histogram::chart(&histograms, bins as usize), chart)?;
+    }
+
+    Ok(())
+  }
+}
+
+/// Describes an image file.
+///
+/// Usage: ```info input.png --json=true```
+pub struct Info;
+
+impl Command for Info {
+  fn name(&self) -> &'static str {
+    "info"
+  }
+
+  fn args(&self) -> &[ArgSpec] {
+    const ARGS: &[ArgSpec] = &[
+      ArgSpec::new("input", ArgKind::Text),
+      ArgSpec::option("json", ArgKind::Bool, "false"),
+    ];
+    ARGS
+  }
+
+  fn help(&self) -> &'static str {
+    "Describes an image: its format, dimensions, color type, bit depth, whether it has alpha, \
+     file size, number of unique colors and mean color. <json> prints it as a JSON object."
+  }
+
+  fn run(&self, args: &OpArgs) -> Result<()> {
+    let info = info::inspect(args.get("input")?)?;
+    if args.get("json")? {
+      print!("{}", info.to_json());
+    } else {
+      print!("{}", info.to_text());
+    }
+
+    Ok(())
+  }
+}
+
+/// Generates a seeded noise texture.
+///
+/// Usage: ```noise 512 512 output.png --seed=7 --octaves=5 --palette=ocean```
+pub struct Noise;
+
+impl Command for Noise {
+  fn name(&self) -> &'static str {
+    "noise"
+  }
+
+  fn args(&self) -> &[ArgSpec] {
+    const ARGS: &[ArgSpec] = &[
+      ArgSpec::new("width", ArgKind::U32),
+      ArgSpec::new("height", ArgKind::U32),
+      ArgSpec::new("output", ArgKind::Text),
+      ArgSpec::option("basis", ArgKind::Text, "perlin"),
+      ArgSpec::option("seed", ArgKind::U64, "0"),
+      ArgSpec::option("scale", ArgKind::F64, "64"),
+      ArgSpec::option("octaves", ArgKind::U32, "1"),
+      ArgSpec::option("persistence", ArgKind::F64, "0.5"),
+      ArgSpec::option("lacunarity", ArgKind::F64, "2"),
+      ArgSpec::option("palette", ArgKind::Text, "grayscale"),
+      ArgSpec::option("threads", ArgKind::U32, "0"),
+    ];
+    ARGS
+  }
+
+  fn saves_image(&self) -> bool {
+    true
+  }
+
+  fn help(&self) -> &'static str {
+    "Generates a <width> x <height> perlin or value noise texture with features <scale> pixels \
+     across. More <octaves> add finer detail, each <lacunarity> times finer and <persistence> \
+     times fainter. The same <seed> always gives the same texture."
+  }
+
+  fn run(&self, args: &OpArgs) -> Result<()> {
+    save_image_with(
+      noise::render(&noise_params(args)?, args.get("threads")?),
+      args.get("output")?,
+      &save_options(args)?,
+    )
+  }
+}
+
+/// Builds and validates `NoiseParams` from the `noise` arguments.
+///
+/// Arguments:
+///
+/// * `args` - &OpArgs
+///
+/// Usage: ```noise_params(args)?;```
+pub fn noise_params(args: &OpArgs) -> Result<NoiseParams> {
+  let subcommand = args.subcommand();
+  let bad_argument =
+    |argument: &str, reason: String| ImageUtilsError::bad_argument(subcommand, argument, reason);
+
+  let params = NoiseParams {
+    basis: args
+      .get::<String>("basis")?
+      .parse()
+      .map_err(|reason| bad_argument("basis", reason))?,
+    width: args.get("width")?,
+    height: args.get("height")?,
+    seed: args.get("seed")?,
+    scale: args.get("scale")?,
+    octaves: args.get("octaves")?,
+    persistence: args.get("persistence")?,
+    lacunarity: args.get("lacunarity")?,
+    palette: args
+      .get::<String>("palette")?
+      .parse()
+      .map_err(|reason| bad_argument("palette", reason))?,
+  };
+
+  let greater_than_zero = "must be greater than 0";
+  let finite_and_positive = "must be a finite number greater than 0";
+  args.check(&[
+    ("width", params.width > 0, greater_than_zero),
+    ("height", params.height > 0, greater_than_zero),
+    (
+      "scale",
+      params.scale > 0.0 && params.scale.is_finite(),
+      finite_and_positive,
+    ),
+    (
+      "persistence",
+      params.persistence > 0.0 && params.persistence.is_finite(),
+      finite_and_positive,
+    ),
+    (
+      "lacunarity",
+      params.lacunarity > 0.0 && params.lacunarity.is_finite(),
+      finite_and_positive,
+    ),
+    (
+      "octaves",
+      (1..=16).contains(&params.octaves),
+      "must be between 1 and 16",
+    ),
+  ])?;
+
+  Ok(params)
+}
+
+/// The output options every subcommand, pipeline and batch that saves an image takes.
+pub const SAVE_ARGS: [ArgSpec; 5] = [
+  ArgSpec::option("format", ArgKind::Text, "auto"),
+  ArgSpec::option("quality", ArgKind::U8, "75"),
+  ArgSpec::option("compression", ArgKind::Text, "fast"),
+  ArgSpec::option("png-filter", ArgKind::Text, "sub"),
+  ArgSpec::option("color", ArgKind::Text, "auto"),
+];
+
+/// Builds and validates `SaveOptions` from the `SAVE_ARGS` options.
+///
+/// Arguments:
+///
+/// * `args` - &OpArgs
+///
+/// Usage: ```save_options(args)?;```
+pub fn save_options(args: &OpArgs) -> Result<SaveOptions> {
+  let subcommand = args.subcommand();
+  let bad_argument =
+    |argument: &str, reason: String| ImageUtilsError::bad_argument(subcommand, argument, reason);
+
+  let jpeg_quality: u8 = args.get("quality")?;
+  args.check(&[(
+    "quality",
+    (1..=100).contains(&jpeg_quality),
+    "must be between 1 and 100",
+  )])?;
+
+  Ok(SaveOptions {
+    format: encode::parse_format(&args.get::<String>("format")?)
+      .map_err(|reason| bad_argument("format", reason))?,
+    jpeg_quality,
+    png_compression: encode::parse_compression(&args.get::<String>("compression")?)
+      .map_err(|reason| bad_argument("compression", reason))?,
+    png_filter: encode::parse_png_filter(&args.get::<String>("png-filter")?)
+      .map_err(|reason| bad_argument("png-filter", reason))?,
+    color: encode::parse_color_type(&args.get::<String>("color")?)
+      .map_err(|reason| bad_argument("color", reason))?,
+  })
+}
@@ -0,0 +1,227 @@
+use std::str::FromStr;
+
+/// A color at a `position` between `0.0` and `1.0` along a palette.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ColorStop {
+  pub position: f64,
+  pub color: [u8; 3],
+}
+
+/// A gradient that maps a value between `0.0` and `1.0` to a color by interpolating between
+/// stops.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Palette {
+  stops: Vec<ColorStop>,
+}
+
+impl Palette {
+  /// The names of the built-in palettes.
+  pub const BUILTINS: [&'static str; 5] = ["fire", "grayscale", "ocean", "rainbow", "twilight"];
+
+  /// Creates a palette from at least two stops, sorting them by position.
+  ///
+  /// Arguments:
+  ///
+  /// * `stops` - Vec<ColorStop>
+  ///
+  /// Usage: ```Palette::new(vec![ColorStop { position: 0.0, color: [0, 0, 0] }, ...])?;```
+  pub fn new(mut stops: Vec<ColorStop>) -> Result<Self, String> {
+    if stops.len() < 2 {
+      return Err("needs at least two color stops".to_string());
+    }
+    if stops
+      .iter()
+      .any(|stop| !(0.0..=1.0).contains(&stop.position))
+    {
+      return Err("has a color stop outside of 0 to 1".to_string());
+    }
+    stops.sort_by(|a, b| {
+      a.position
+        .partial_cmp(&b.position)
+        .expect("positions are finite")
+    });
+
+    Ok(Palette { stops })
+  }
+
+  /// Looks up a built-in palette by name.
+  ///
+  /// Arguments:
+  ///
+  /// * `name` - &str
+  ///
+  /// Usage: ```Palette::builtin("fire");```
+  pub fn builtin(name: &str) -> Option<Self> {
+    let stops: &[(f64, [u8; 3])] = match name {
+      "fire" => &[
+        (0.0, [0, 0, 0]),
+        (0.3, [128, 0, 0]),
+        (0.6, [255, 128, 0]),
+        (0.85, [255, 230, 80]),
+        (1.0, [255, 255, 255]),
+      ],
+      "grayscale" => &[(0.0, [0, 0, 0]), (1.0, [255, 255, 255])],
+      "ocean" => &[
+        (0.0, [0, 7, 100]),
+        (0.16, [32, 107, 203]),
+        (0.42, [237, 255, 255]),
+        (0.64, [255, 170, 0]),
+        (0.86, [0, 2, 0]),
+        (1.0, [0, 7, 100]),
+      ],
+      "rainbow" => &[
+        (0.0, [255, 0, 0]),
+        (0.17, [255, 165, 0]),
+        (0.33, [255, 255, 0]),
+        (0.5, [0, 255, 0]),
+        (0.67, [0, 0, 255]),
+        (0.83, [75, 0, 130]),
+        (1.0, [238, 130, 238]),
+      ],
+      "twilight" => &[
+        (0.0, [30, 10, 60]),
+        (0.5, [220, 90, 120]),
+        (1.0, [250, 220, 160]),
+      ],
+      _ => return None,
+    };
+
+    Some(Palette {
+      stops: stops
+        .iter()
+        .map(|&(position, color)| ColorStop { position, color })
+        .collect(),
+    })
+  }
+
+  /// Samples the palette at `t`, clamped to `0.0..=1.0`.
+  ///
+  /// Arguments:
+  ///
+  /// * `t` - f64
+  ///
+  /// Usage: ```palette.sample(0.5);```
+  pub fn sample(&self, t: f64) -> [u8; 3] {
+    let t = if t.is_nan() { 0.0 } else { t.clamp(0.0, 1.0) };
+    let first = self.stops[0];
+    let last = self.stops[self.stops.len() - 1];
+    if t <= first.position {
+      return first.color;
+    }
+    if t >= last.position {
+      return last.color;
+    }
+
+    let next = self
+      .stops
+      .iter()
+      .position(|stop| stop.position >= t)
+      .unwrap_or(self.stops.len() - 1);
+    let (from, to) = (self.stops[next - 1], self.stops[next]);
+    let span = to.position - from.position;
+    let mix = if span > 0.0 {
+      (t - from.position) / span
+    } else {
+      1.0
+    };
+
+    let mut color = [0; 3];
+    for (channel, value) in color.iter_mut().enumerate() {
+      let (a, b) = (f64::from(from.color[channel]), f64::from(to.color[channel]));
+      *value = (a + (b - a) * mix).round() as u8;
+    }
+
+    color
+  }
+}
+
+impl FromStr for Palette {
+  type Err = String;
+
+  /// Parses a built-in palette name, or comma separated `position:color` stops such as
+  /// `0:000000,0.5:ff8800,1:ffffff`.
+  fn from_str(spec: &str) -> Result<Self, Self::Err> {
+    if let Some(palette) = Palette::builtin(spec) {
+      return Ok(palette);
+    }
+
+    let invalid = || {
+      format!(
+        "must be one of {} or stops like 0:000000,1:ffffff",
+        Palette::BUILTINS.join(", ")
+      )
+    };
+    let mut stops = Vec::new();
+    for stop in spec.split(',') {
+      let mut parts = stop.splitn(2, ':');
+      let position = parts
+        .next()
+        .and_then(|position| position.trim().parse::<f64>().ok())
+        .ok_or_else(invalid)?;
+      let color = parts.next().and_then(parse_color).ok_or_else(invalid)?;
+      stops.push(ColorStop { position, color });
+    }
+
+    Palette::new(stops)
+  }
+}
+
+/// Parses a hex color such as `ff8800` or `#ff8800`.
+///
+/// Arguments:
+///
+/// * `hex` - &str
+///
+/// Usage: ```parse_color("#ff8800");```
+pub fn parse_color(hex: &str) -> Option<[u8; 3]> {
+  let hex = hex.trim().trim_start_matches('#');
+  if hex.len() != 6 || !hex.is_ascii() {
+    return None;
+  }
+
+  let mut color = [0; 3];
+  for (channel, value) in color.iter_mut().enumerate() {
+    *value = u8::from_str_radix(&hex[channel * 2..channel * 2 + 2], 16).ok()?;
+  }
+
+  Some(color)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn colors_are_six_hex_digits_with_an_optional_hash() {
+    assert_eq!(parse_color("ff8000"), Some([255, 128, 0]));
+    assert_eq!(parse_color(" #0A0b0C "), Some([10, 11, 12]));
+    assert_eq!(parse_color("fff"), None);
+    assert_eq!(parse_color("ff80zz"), None);
+    assert_eq!(parse_color("ff80é"), None);
+  }
+
+  #[test]
+  fn samples_blend_between_the_surrounding_stops() {
+    let palette: Palette = "1:ffffff,0:000000,0.5:ff0000".parse().unwrap();
+
+    assert_eq!(palette.sample(0.0), [0, 0, 0]);
+    assert_eq!(palette.sample(0.25), [128, 0, 0]);
+    assert_eq!(palette.sample(0.5), [255, 0, 0]);
+    assert_eq!(palette.sample(0.75), [255, 128, 128]);
+    assert_eq!(palette.sample(1.0), [255, 255, 255]);
+    // Out of range and NaN samples are clamped to the ends.
+    assert_eq!(palette.sample(-3.0), [0, 0, 0]);
+    assert_eq!(palette.sample(7.0), [255, 255, 255]);
+    assert_eq!(palette.sample(f64::NAN), [0, 0, 0]);
+  }
+
+  #[test]
+  fn palettes_need_two_stops_inside_zero_to_one() {
+    for name in Palette::BUILTINS.iter() {
+      assert!(name.parse::<Palette>().is_ok(), "{}", name);
+    }
+    assert!("0:000000".parse::<Palette>().is_err());
+    assert!("0:000000,1.5:ffffff".parse::<Palette>().is_err());
+    assert!("0:000000,1:white".parse::<Palette>().is_err());
+  }
+}
@@ -0,0 +1,189 @@
+use crate::palette::Palette;
+use crate::render::{par_render, solid};
+use image::DynamicImage;
+use std::fmt;
+use std::str::FromStr;
+
+/// The images `generate` can draw. Gradients sample the whole palette; the other patterns
+/// alternate between its first and last colors.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Pattern {
+  /// The first color everywhere.
+  Solid,
+  /// A gradient running across the image in the direction of the angle.
+  Linear,
+  /// A gradient running from the middle of the image out to its corners.
+  Radial,
+  /// Alternating squares `size` pixels wide.
+  Checkerboard,
+  /// Alternating bands `size` pixels wide, running across the direction of the angle.
+  Stripes,
+  /// Lines `line_width` pixels wide every `size` pixels, over the first color.
+  Grid,
+}
+
+impl Pattern {
+  /// The names `Pattern::from_str` accepts.
+  pub const NAMES: [&'static str; 6] = [
+    "solid",
+    "linear",
+    "radial",
+    "checkerboard",
+    "stripes",
+    "grid",
+  ];
+}
+
+impl FromStr for Pattern {
+  type Err = String;
+
+  fn from_str(name: &str) -> Result<Self, Self::Err> {
+    match name {
+      "solid" => Ok(Pattern::Solid),
+      "linear" => Ok(Pattern::Linear),
+      "radial" => Ok(Pattern::Radial),
+      "checkerboard" => Ok(Pattern::Checkerboard),
+      "stripes" => Ok(Pattern::Stripes),
+      "grid" => Ok(Pattern::Grid),
+      _ => Err(format!("must be one of {}", Pattern::NAMES.join(", "))),
+    }
+  }
+}
+
+impl fmt::Display for Pattern {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      Pattern::Solid => write!(f, "solid"),
+      Pattern::Linear => write!(f, "linear"),
+      Pattern::Radial => write!(f, "radial"),
+      Pattern::Checkerboard => write!(f, "checkerboard"),
+      Pattern::Stripes => write!(f, "stripes"),
+      Pattern::Grid => write!(f, "grid"),
+    }
+  }
+}
+
+/// Everything that determines what a generated pattern looks like.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PatternParams {
+  pub pattern: Pattern,
+  pub width: u32,
+  pub height: u32,
+  pub palette: Palette,
+  /// The direction of linear gradients and stripes in degrees, clockwise from left to right.
+  pub angle: f64,
+  /// The width of checkerboard squares, stripes and grid cells in pixels.
+  pub size: u32,
+  /// The width of grid lines in pixels.
+  pub line_width: u32,
+}
+
+/// Renders a pattern, spreading rows over `threads` workers.
+///
+/// Arguments:
+///
+/// * `params` - &PatternParams
+/// * `threads` - usize (0 uses every core)
+///
+/// Usage: ```pattern::render(&params, 0);```
+pub fn render(params: &PatternParams, threads: usize) -> DynamicImage {
+  let (width, height) = (params.width, params.height);
+  let palette = &params.palette;
+  let (background, foreground) = (palette.sample(0.0), palette.sample(1.0));
+  let cell = params.size.max(1);
+  let size = f64::from(cell);
+  let (sin, cos) = params.angle.to_radians().sin_cos();
+  // How far along the angle a position is.
+  let along = |x: f64, y: f64| x * cos + y * sin;
+  // Linear gradients run from the corner furthest back along the angle to the one furthest on.
+  let corners = [
+    along(0.0, 0.0),
+    along(f64::from(width), 0.0),
+    along(0.0, f64::from(height)),
+    along(f64::from(width), f64::from(height)),
+  ];
+  let start = corners.iter().cloned().fold(f64::INFINITY, f64::min);
+  let end = corners.iter().cloned().fold(f64::NEG_INFINITY, f64::max);
+
+  let pixel = |x: u32, y: u32| -> [u8; 3] {
+    // Sample the middle of the pixel so patterns are symmetric.
+    let (px, py) = (f64::from(x) + 0.5, f64::from(y) + 0.5);
+    let alternate = |on: bool| if on { foreground } else { background };
+
+    match params.pattern {
+      Pattern::Solid => background,
+      Pattern::Linear => palette.sample((along(px, py) - start) / (end - start)),
+      Pattern::Radial => {
+        let (cx, cy) = (f64::from(width) / 2.0, f64::from(height) / 2.0);
+
+        palette.sample((px - cx).hypot(py - cy) / cx.hypot(cy))
+      }
+      Pattern::Checkerboard => alternate((x / cell + y / cell) % 2 == 1),
+      Pattern::Stripes => alternate((along(px, py) / size).floor().rem_euclid(2.0) >= 1.0),
+      Pattern::Grid => alternate(x % cell < params.line_width || y % cell < params.line_width),
+    }
+  };
+
+  match params.pattern {
+    Pattern::Solid => solid(width, height, background),
+    _ => DynamicImage::ImageRgb8(par_render(width, height, threads, pixel)),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn params(pattern: Pattern) -> PatternParams {
+    PatternParams {
+      pattern,
+      width: 8,
+      height: 4,
+      palette: Palette::builtin("grayscale").unwrap(),
+      angle: 0.0,
+      size: 2,
+      line_width: 1,
+    }
+  }
+
+  fn row(pattern: &PatternParams, y: u32) -> Vec<u8> {
+    let image = render(pattern, 3).to_rgb8();
+    (0..pattern.width)
+      .map(|x| image.get_pixel(x, y)[0])
+      .collect()
+  }
+
+  #[test]
+  fn alternating_patterns_switch_every_size_pixels() {
+    let checkerboard = params(Pattern::Checkerboard);
+    assert_eq!(row(&checkerboard, 0), [0, 0, 255, 255, 0, 0, 255, 255]);
+    assert_eq!(row(&checkerboard, 2), [255, 255, 0, 0, 255, 255, 0, 0]);
+
+    let grid = params(Pattern::Grid);
+    assert_eq!(row(&grid, 0), [255; 8]);
+    assert_eq!(row(&grid, 1), [255, 0, 255, 0, 255, 0, 255, 0]);
+
+    let stripes = PatternParams {
+      angle: 90.0,
+      ..params(Pattern::Stripes)
+    };
+    assert_eq!(row(&stripes, 0), [0; 8]);
+    assert_eq!(row(&stripes, 2), [255; 8]);
+  }
+
+  #[test]
+  fn linear_gradients_run_from_the_first_color_to_the_last() {
+    let gradient = row(&params(Pattern::Linear), 0);
+
+    assert!(gradient.windows(2).all(|pair| pair[0] < pair[1]));
+    assert_eq!(gradient[0], 16);
+    assert_eq!(gradient[7], 239);
+    let reversed = PatternParams {
+      angle: 180.0,
+      ..params(Pattern::Linear)
+    };
+    let mut backwards = row(&reversed, 0);
+    backwards.reverse();
+    assert_eq!(backwards, gradient);
+  }
+}
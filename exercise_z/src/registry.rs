@@ -0,0 +1,575 @@
+use crate::batch::{self, FileOutcome};
+use crate::op::{is_option, ArgKind, ArgSpec, Command, ImageOp, OpArgs};
+use crate::ops::{self, save_options, SAVE_ARGS};
+use crate::{open_image, save_image_with, ImageUtilsError, Result};
+use std::iter::{self, Peekable};
+use std::path::Path;
+
+/// The input and output paths every `ImageOp` subcommand takes after its own arguments.
+const PATH_ARGS: [ArgSpec; 2] = [
+  ArgSpec::new("input", ArgKind::Text),
+  ArgSpec::new("output", ArgKind::Text),
+];
+
+/// The arguments `batch` takes before its stages.
+const BATCH_ARGS: [ArgSpec; 3] = [
+  ArgSpec::new("input", ArgKind::Text),
+  ArgSpec::new("output", ArgKind::Text),
+  ArgSpec::option("threads", ArgKind::U32, "0"),
+];
+
+/// How `pipeline` and `batch` take their output options and stages, after their own arguments.
+const STAGES_USAGE: &str = "[<output options>] <stage> [<stage arguments>] ...";
+
+/// Every operation and command the CLI knows about. Drives dispatch, argument validation and
+/// usage output.
+#[derive(Default)]
+pub struct Registry {
+  ops: Vec<Box<dyn ImageOp>>,
+  commands: Vec<Box<dyn Command>>,
+}
+
+impl Registry {
+  /// Creates an empty registry.
+  pub fn new() -> Self {
+    Registry::default()
+  }
+
+  /// Creates a registry with every built-in operation and command.
+  pub fn with_builtins() -> Self {
+    let mut registry = Registry::new();
+    ops::register_builtins(&mut registry);
+    registry
+  }
+
+  /// Registers an image-to-image operation, replacing any operation or command with the same name.
+  ///
+  /// Arguments:
+  ///
+  /// * `op` - impl ImageOp
+  ///
+  /// Usage: ```registry.register_op(Blur);```
+  pub fn register_op(&mut self, op: impl ImageOp + 'static) {
+    self.remove(op.name());
+    self.ops.push(Box::new(op));
+  }
+
+  /// Registers a command, replacing any operation or command with the same name.
+  ///
+  /// Arguments:
+  ///
+  /// * `command` - impl Command
+  ///
+  /// Usage: ```registry.register_command(Fractal);```
+  pub fn register_command(&mut self, command: impl Command + 'static) {
+    self.remove(command.name());
+    self.commands.push(Box::new(command));
+  }
+
+  fn remove(&mut self, name: &str) {
+    self.ops.retain(|op| op.name() != name);
+    self.commands.retain(|command| command.name() != name);
+  }
+
+  /// Looks up an image-to-image operation by name.
+  pub fn op(&self, name: &str) -> Option<&dyn ImageOp> {
+    self
+      .ops
+      .iter()
+      .find(|op| op.name() == name)
+      .map(|op| op.as_ref())
+  }
+
+  /// Looks up a command by name.
+  pub fn command(&self, name: &str) -> Option<&dyn Command> {
+    self
+      .commands
+      .iter()
+      .find(|command| command.name() == name)
+      .map(|command| command.as_ref())
+  }
+
+  /// Runs the subcommand or pipeline described by command-line `args`. Registered operations and
+  /// commands are looked up first, so they can't be shadowed by a file name or by the built-in
+  /// `help` and `pipeline` subcommands. Anything else whose third argument is a stage, such as
+  /// `input.png output.png blur 2.5`, runs as a pipeline.
+  ///
+  /// Arguments:
+  ///
+  /// * `args` - Vec<String>
+  ///
+  /// Usage: ```registry.run(vec!["blur", "2.5", "input.png", "output.png"])?;```
+  pub fn run(&self, mut args: Vec<String>) -> Result<()> {
+    if args.is_empty() {
+      self.print_usage();
+      return Ok(());
+    }
+
+    let name = args.remove(0);
+    if let Some(command) = self.command(&name) {
+      let args = OpArgs::parse(&name, &command_args(command), args)?;
+      return command.run(&args);
+    }
+
+    if let Some(op) = self.op(&name) {
+      let specs: Vec<ArgSpec> = op
+        .args()
+        .iter()
+        .chain(PATH_ARGS.iter())
+        .chain(SAVE_ARGS.iter())
+        .copied()
+        .collect();
+      let op_args = OpArgs::parse(&name, &specs, args)?;
+      let options = save_options(&op_args)?;
+      let img = open_image(op_args.get("input")?)?;
+
+      return save_image_with(op.apply(&img, &op_args)?, op_args.get("output")?, &options);
+    }
+
+    match name.as_str() {
+      "help" => match args.first() {
+        Some(subcommand) => self.print_help(subcommand),
+        None => {
+          self.print_usage();
+          Ok(())
+        }
+      },
+      "pipeline" => self.pipeline(args),
+      "batch" => self.batch(args),
+      // `input.png output.png <stage> ...` runs every stage against a single decode.
+      _ if self.is_pipeline(&name, &args) => {
+        args.insert(0, name);
+        self.pipeline(args)
+      }
+      _ => Err(ImageUtilsError::UnknownSubcommand(name)),
+    }
+  }
+
+  /// Whether `first` and `rest` spell `<input> <output> <stage> ...`, with any `--name=value`
+  /// options around the paths.
+  fn is_pipeline(&self, first: &str, rest: &[String]) -> bool {
+    iter::once(first)
+      .chain(rest.iter().map(String::as_str))
+      .filter(|value| !is_option(value))
+      .nth(2)
+      .is_some_and(|stage| self.op(stage).is_some())
+  }
+
+  /// Decodes an image once, runs every stage in order in memory and encodes the result once.
+  ///
+  /// Arguments:
+  ///
+  /// * `args` - Vec<String> (`<input> <output> [--option=value ...] <stage> [<stage arguments>] ...`)
+  ///
+  /// Usage: ```registry.pipeline(vec!["input.png", "output.jpg", "--quality=90", "blur", "2.5"])?;```
+  ///
+  /// On the command line: ```input.png output.jpg --quality=90 blur 2.5 invert```, or the same
+  /// after `pipeline`.
+  pub fn pipeline(&self, args: Vec<String>) -> Result<()> {
+    let mut args = args.into_iter().peekable();
+    let specs: Vec<ArgSpec> = PATH_ARGS.iter().chain(SAVE_ARGS.iter()).copied().collect();
+    let pipeline_args = OpArgs::parse("pipeline", &specs, take_paths("pipeline", &mut args)?)?;
+    let options = save_options(&pipeline_args)?;
+    let stages = self.parse_stages(args.collect())?;
+    if stages.is_empty() {
+      return Err(ImageUtilsError::bad_argument(
+        "pipeline",
+        "stage",
+        "is missing",
+      ));
+    }
+
+    let mut img = open_image(pipeline_args.get("input")?)?;
+    for (op, op_args) in stages {
+      img = op.apply(&img, &op_args)?;
+    }
+
+    save_image_with(img, pipeline_args.get("output")?, &options)
+  }
+
+  /// Validates every stage up front so a typo doesn't cost an image decode.
+  ///
+  /// Arguments:
+  ///
+  /// * `args` - Vec<String> (`<stage> [<stage arguments>] ...`)
+  ///
+  /// Usage: ```registry.parse_stages(vec!["blur", "2.5", "invert"])?;```
+  pub fn parse_stages(&self, args: Vec<String>) -> Result<Vec<(&dyn ImageOp, OpArgs)>> {
+    let mut stages = Vec::new();
+    let mut args = args.into_iter().peekable();
+
+    while let Some(name) = args.next() {
+      let op = self
+        .op(&name)
+        .ok_or_else(|| ImageUtilsError::UnknownSubcommand(name.clone()))?;
+      // A stage owns its positional arguments plus any `--name=value` options around them.
+      let required = op.args().iter().filter(|spec| !spec.is_option()).count();
+      let values = take_leading(&mut args, required);
+      stages.push((op, OpArgs::parse(&name, op.args(), values)?));
+    }
+
+    Ok(stages)
+  }
+
+  /// Runs stages over a directory or glob of images on a pool of worker threads, reporting every
+  /// file's outcome and a summary instead of stopping at the first failure.
+  ///
+  /// Arguments:
+  ///
+  /// * `args` - Vec<String> (`<input> <output> [--threads=<n>] <stage> [<stage arguments>] ...`)
+  ///
+  /// Usage: ```registry.batch(vec!["photos/*.png", "out", "--threads=4", "grayscale"])?;```
+  pub fn batch(&self, args: Vec<String>) -> Result<()> {
+    let mut args = args.into_iter().peekable();
+    let specs: Vec<ArgSpec> = BATCH_ARGS.iter().chain(SAVE_ARGS.iter()).copied().collect();
+    let batch_args = OpArgs::parse("batch", &specs, take_paths("batch", &mut args)?)?;
+    let options = save_options(&batch_args)?;
+    let stages = self.parse_stages(args.collect())?;
+    if stages.is_empty() {
+      return Err(ImageUtilsError::bad_argument(
+        "batch",
+        "stage",
+        "is missing",
+      ));
+    }
+
+    let inputs = batch::find_inputs(&batch_args.get::<String>("input")?)?;
+    let output_dir: String = batch_args.get("output")?;
+    let threads: usize = batch_args.get("threads")?;
+    let total = inputs.len();
+
+    let summary = batch::process(
+      &stages,
+      inputs,
+      Path::new(&output_dir),
+      threads,
+      &options,
+      |outcome: &FileOutcome| match &outcome.result {
+        Ok(()) => println!(
+          "\x1b[32m[OK]\x1b[0m {} -> {}",
+          outcome.input.display(),
+          outcome.output.display()
+        ),
+        Err(error) => println!(
+          "\x1b[31m[FAILED]\x1b[0m {}: {}",
+          outcome.input.display(),
+          error
+        ),
+      },
+    )?;
+
+    println!(
+      "\nProcessed {} file(s): {} succeeded, {} failed.",
+      total, summary.succeeded, summary.failed
+    );
+
+    if summary.failed > 0 {
+      return Err(ImageUtilsError::BatchFailed {
+        failed: summary.failed,
+        total,
+      });
+    }
+
+    Ok(())
+  }
+
+  /// Prints every subcommand and its arguments.
+  pub fn print_commands(&self) {
+    println!("subcommand   <arguments>\n");
+
+    let mut lines: Vec<(&str, String, &str)> = self
+      .ops
+      .iter()
+      .map(|op| {
+        (
+          op.name(),
+          usage_args(op.args().iter().chain(PATH_ARGS.iter())),
+          op.help(),
+        )
+      })
+      .chain(self.commands.iter().map(|command| {
+        (
+          command.name(),
+          usage_args(command_args(command.as_ref()).iter()),
+          command.help(),
+        )
+      }))
+      .collect();
+    lines.sort_by_key(|(name, _, _)| *name);
+
+    for (name, args, help) in lines {
+      println!("{:<12} {}", name, args);
+      println!("{:<12} {}", "", help);
+    }
+
+    let mut stages: Vec<&str> = self.ops.iter().map(|op| op.name()).collect();
+    stages.sort_unstable();
+    println!();
+    println!(
+      "pipeline     {} {}",
+      usage_args(PATH_ARGS.iter()),
+      STAGES_USAGE
+    );
+    println!("             stages: {}", stages.join(", "));
+    println!("             e.g. input.png output.png blur 2.5 invert rotate 180 brighten 10");
+    println!("             The pipeline subcommand name in front is optional.");
+    println!();
+    println!(
+      "batch        {} {}",
+      usage_args(BATCH_ARGS.iter()),
+      STAGES_USAGE
+    );
+    println!("             Runs stages over a directory or glob of images, e.g. \"photos/*.png\".");
+    println!();
+    println!("output       {}", usage_args(SAVE_ARGS.iter()));
+    println!(
+      "             Output options for every operation, pipeline, batch, fractal, generate and noise. \
+       <format> forces png, jpg, bmp, tiff, etc. instead of following the output's extension. \
+       <quality> (1 to 100) is for JPEG; <compression> (fast, default, best, huffman or rle) and \
+       <png-filter> (none, sub, up, avg or paeth) are for PNG. <color> converts to l8, la8, \
+       rgb8, rgba8, l16, la16, rgb16 or rgba16 first. histogram writes data rather than an \
+       image and takes <export> instead."
+    );
+    println!();
+  }
+
+  /// Prints application usage information.
+  pub fn print_usage(&self) {
+    println!("\nImage Manipulator");
+    println!("Matt Carlotta <matt@mattcarlotta.sh>");
+    println!("Manipulates images using the CLI\n");
+    self.print_commands();
+  }
+
+  /// Prints the usage of a single subcommand.
+  ///
+  /// Arguments:
+  ///
+  /// * `name` - &str
+  ///
+  /// Usage: ```registry.print_help("crop")?;```
+  pub fn print_help(&self, name: &str) -> Result<()> {
+    let (args, help) = if let Some(op) = self.op(name) {
+      (
+        usage_args(op.args().iter().chain(PATH_ARGS.iter())),
+        op.help(),
+      )
+    } else if let Some(command) = self.command(name) {
+      (usage_args(command_args(command).iter()), command.help())
+    } else if name == "pipeline" {
+      (
+        format!("{} {}", usage_args(PATH_ARGS.iter()), STAGES_USAGE),
+        "Decodes <input> once, runs every stage in order in memory and encodes <output> once. \
+         The pipeline subcommand name in front is optional.",
+      )
+    } else if name == "batch" {
+      (
+        format!("{} {}", usage_args(BATCH_ARGS.iter()), STAGES_USAGE),
+        "Runs stages over a directory or glob of images, e.g. \"photos/*.png\".",
+      )
+    } else {
+      return Err(ImageUtilsError::UnknownSubcommand(name.to_string()));
+    };
+
+    println!("\n{} {}\n\n{}\n", name, args, help);
+    Ok(())
+  }
+}
+
+/// Takes `count` positional arguments off the front of `args`, along with any `--name=value`
+/// options before, between or straight after them.
+fn take_leading(args: &mut Peekable<impl Iterator<Item = String>>, count: usize) -> Vec<String> {
+  let mut values = Vec::new();
+  let mut positionals = 0;
+  while let Some(value) = args.next_if(|value| positionals < count || is_option(value)) {
+    if !is_option(&value) {
+      positionals += 1;
+    }
+    values.push(value);
+  }
+
+  values
+}
+
+/// Takes the `PATH_ARGS` a `subcommand` expects before its stages, along with the options around
+/// them, so options can come before the paths without being counted as one.
+fn take_paths(
+  subcommand: &str,
+  args: &mut Peekable<impl Iterator<Item = String>>,
+) -> Result<Vec<String>> {
+  let values = take_leading(args, PATH_ARGS.len());
+  let paths = values.iter().filter(|value| !is_option(value)).count();
+  match PATH_ARGS.get(paths) {
+    Some(missing) => Err(ImageUtilsError::bad_argument(
+      subcommand,
+      missing.name,
+      "is missing",
+    )),
+    None => Ok(values),
+  }
+}
+
+/// A command's own arguments, plus the `SAVE_ARGS` output options if it saves an image.
+fn command_args(command: &dyn Command) -> Vec<ArgSpec> {
+  let save_args: &[ArgSpec] = if command.saves_image() {
+    &SAVE_ARGS
+  } else {
+    &[]
+  };
+
+  command
+    .args()
+    .iter()
+    .chain(save_args.iter())
+    .copied()
+    .collect()
+}
+
+/// Formats argument specs as `<name(type)> ... [--name(type)=default] ...`.
+fn usage_args<'a>(specs: impl Iterator<Item = &'a ArgSpec>) -> String {
+  let (options, positionals): (Vec<&ArgSpec>, Vec<&ArgSpec>) =
+    specs.partition(|spec| spec.is_option());
+
+  positionals
+    .into_iter()
+    .chain(options)
+    .map(|spec| spec.to_string())
+    .collect::<Vec<_>>()
+    .join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  /// A command that fails with its own name, so a test can tell it ran.
+  struct Marker(&'static str);
+
+  impl Command for Marker {
+    fn name(&self) -> &'static str {
+      self.0
+    }
+
+    fn args(&self) -> &[ArgSpec] {
+      &[]
+    }
+
+    fn help(&self) -> &'static str {
+      "Fails with its own name."
+    }
+
+    fn run(&self, _args: &OpArgs) -> Result<()> {
+      Err(ImageUtilsError::bad_argument(self.0, "marker", "ran"))
+    }
+  }
+
+  fn strings(values: &[&str]) -> Vec<String> {
+    values.iter().map(|value| value.to_string()).collect()
+  }
+
+  fn ran(result: Result<()>, name: &str) -> bool {
+    matches!(result, Err(ImageUtilsError::BadArgument { ref subcommand, .. }) if subcommand == name)
+  }
+
+  #[test]
+  fn registered_commands_win_over_builtins_and_pipelines() {
+    let mut registry = Registry::with_builtins();
+    registry.register_command(Marker("help"));
+    registry.register_command(Marker("in.png"));
+
+    assert!(ran(registry.run(strings(&["help"])), "help"));
+    assert!(ran(registry.run(strings(&["in.png"])), "in.png"));
+  }
+
+  #[test]
+  fn a_stage_after_two_paths_runs_a_pipeline() {
+    let registry = Registry::with_builtins();
+    let is_missing_input = |result: Result<()>| matches!(result, Err(ImageUtilsError::Io { ref path, .. }) if path == "missing.png");
+
+    assert!(is_missing_input(registry.run(strings(&[
+      "missing.png",
+      "out.png",
+      "invert"
+    ]))));
+    assert!(is_missing_input(registry.run(strings(&[
+      "pipeline",
+      "missing.png",
+      "out.png",
+      "blur",
+      "2.5",
+      "invert"
+    ]))));
+  }
+
+  #[test]
+  fn anything_else_is_an_unknown_subcommand() {
+    let registry = Registry::with_builtins();
+
+    assert!(matches!(
+      registry.run(strings(&["missing.png", "out.png", "sparkle"])),
+      Err(ImageUtilsError::UnknownSubcommand(ref name)) if name == "missing.png"
+    ));
+    assert!(matches!(
+      registry.run(strings(&["pipeline", "missing.png", "out.png", "sparkle"])),
+      Err(ImageUtilsError::UnknownSubcommand(ref name)) if name == "sparkle"
+    ));
+  }
+
+  #[test]
+  fn batch_options_can_come_before_the_paths() {
+    let registry = Registry::with_builtins();
+
+    assert!(matches!(
+      registry.run(strings(&["batch", "--threads=2", "missing.png", "out", "grayscale"])),
+      Err(ImageUtilsError::BadArgument { ref argument, .. }) if argument == "input"
+    ));
+    assert!(matches!(
+      registry.run(strings(&["batch", "--threads=2", "missing.png"])),
+      Err(ImageUtilsError::BadArgument { ref argument, ref reason, .. })
+        if argument == "output" && reason == "is missing"
+    ));
+    assert!(matches!(
+      registry.run(strings(&["batch", "missing.png", "out"])),
+      Err(ImageUtilsError::BadArgument { ref argument, .. }) if argument == "stage"
+    ));
+  }
+  #[test]
+  fn output_options_are_checked_before_the_input_is_read() {
+    let registry = Registry::with_builtins();
+    let is_bad = |result: Result<()>, name: &str| matches!(result, Err(ImageUtilsError::BadArgument { ref argument, .. }) if argument == name);
+
+    assert!(is_bad(
+      registry.run(strings(&["invert", "missing.png", "out", "--format=txt"])),
+      "format"
+    ));
+    assert!(is_bad(
+      registry.run(strings(&[
+        "missing.png",
+        "out.jpg",
+        "--quality=0",
+        "invert"
+      ])),
+      "quality"
+    ));
+    assert!(is_bad(
+      registry.run(strings(&[
+        "pipeline",
+        "--color=rgb32f",
+        "missing.png",
+        "out.png",
+        "invert"
+      ])),
+      "color"
+    ));
+    assert!(is_bad(
+      registry.run(strings(&[
+        "batch",
+        "missing.png",
+        "out",
+        "--png-filter=avg2",
+        "invert"
+      ])),
+      "png-filter"
+    ));
+  }
+}
@@ -0,0 +1,97 @@
+use crate::resolve_threads;
+use crossbeam::channel;
+use image::{DynamicImage, ImageBuffer, RgbImage};
+
+/// Renders a `width` x `height` RGB image by calling `pixel` for every coordinate, handing rows
+/// out to `threads` workers as they become free. Every pixel is computed independently, so the
+/// output is identical no matter how many threads are used.
+///
+/// Arguments:
+///
+/// * `width` - u32
+/// * `height` - u32
+/// * `threads` - usize (0 uses every core)
+/// * `pixel` - impl Fn(u32, u32) -> [u8; 3] + Sync
+///
+/// Usage: ```render::par_render(800, 800, 0, |x, y| [x as u8, y as u8, 0]);```
+pub fn par_render(
+  width: u32,
+  height: u32,
+  threads: usize,
+  pixel: impl Fn(u32, u32) -> [u8; 3] + Sync,
+) -> RgbImage {
+  let mut imgbuf: RgbImage = ImageBuffer::new(width, height);
+  if width == 0 || height == 0 {
+    return imgbuf;
+  }
+
+  let threads = resolve_threads(threads).min(height as usize);
+  let row_len = width as usize * 3;
+  let pixel = &pixel;
+
+  // The queued rows borrow `imgbuf`, so the channel has to be gone before it's returned.
+  {
+    let (row_tx, row_rx) = channel::unbounded();
+    for (y, row) in imgbuf.chunks_mut(row_len).enumerate() {
+      row_tx
+        .send((y as u32, row))
+        .expect("the row channel is open");
+    }
+    drop(row_tx);
+
+    crossbeam::scope(|scope| {
+      for _ in 0..threads {
+        let row_rx = row_rx.clone();
+        scope.spawn(move |_| {
+          for (y, row) in row_rx {
+            for (x, rgb) in row.chunks_mut(3).enumerate() {
+              rgb.copy_from_slice(&pixel(x as u32, y));
+            }
+          }
+        });
+      }
+    })
+    .expect("a render worker panicked");
+  }
+
+  imgbuf
+}
+
+/// Renders a `width` x `height` image filled with a single `[red, green, blue]` color.
+///
+/// Arguments:
+///
+/// * `width` - u32
+/// * `height` - u32
+/// * `rgb` - [u8; 3]
+///
+/// Usage: ```render::solid(640, 480, [0, 0, 0]);```
+pub fn solid(width: u32, height: u32, rgb: [u8; 3]) -> DynamicImage {
+  DynamicImage::ImageRgb8(ImageBuffer::from_pixel(width, height, image::Rgb(rgb)))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn every_pixel_lands_in_place_for_any_thread_count() {
+    let pixel = |x: u32, y: u32| [x as u8, y as u8, (x * y) as u8];
+    let expected = ImageBuffer::from_fn(5, 3, |x, y| image::Rgb(pixel(x, y)));
+
+    for &threads in [0, 1, 2, 3, 8].iter() {
+      assert_eq!(
+        par_render(5, 3, threads, pixel),
+        expected,
+        "{} threads",
+        threads
+      );
+    }
+  }
+
+  #[test]
+  fn empty_images_render_without_workers() {
+    assert_eq!(par_render(0, 3, 4, |_, _| [0; 3]).dimensions(), (0, 3));
+    assert_eq!(par_render(3, 0, 4, |_, _| [0; 3]).dimensions(), (3, 0));
+  }
+}
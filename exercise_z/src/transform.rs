@@ -0,0 +1,884 @@
+use crate::{ImageUtilsError, Result};
+use image::imageops::FilterType;
+use image::{DynamicImage, GenericImageView, ImageBuffer, Primitive, Rgba};
+use std::fmt;
+use std::str::FromStr;
+
+/// Runs `$action` on the pixel buffer inside a `DynamicImage`, whatever its color type, and wraps
+/// the resulting buffer back up in the same variant.
+macro_rules! map_buffer {
+  ($img:expr, $buffer:ident => $action:expr) => {
+    match $img {
+      DynamicImage::ImageLuma8($buffer) => DynamicImage::ImageLuma8($action),
+      DynamicImage::ImageLumaA8($buffer) => DynamicImage::ImageLumaA8($action),
+      DynamicImage::ImageRgb8($buffer) => DynamicImage::ImageRgb8($action),
+      DynamicImage::ImageRgba8($buffer) => DynamicImage::ImageRgba8($action),
+      DynamicImage::ImageBgr8($buffer) => DynamicImage::ImageBgr8($action),
+      DynamicImage::ImageBgra8($buffer) => DynamicImage::ImageBgra8($action),
+      DynamicImage::ImageLuma16($buffer) => DynamicImage::ImageLuma16($action),
+      DynamicImage::ImageLumaA16($buffer) => DynamicImage::ImageLumaA16($action),
+      DynamicImage::ImageRgb16($buffer) => DynamicImage::ImageRgb16($action),
+      DynamicImage::ImageRgba16($buffer) => DynamicImage::ImageRgba16($action),
+    }
+  };
+}
+
+/// A rectangle to crop out of an image, in pixels from the top-left corner.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CropRect {
+  pub x: u32,
+  pub y: u32,
+  pub width: u32,
+  pub height: u32,
+}
+
+/// The clockwise rotations that don't need any resampling.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Rotation {
+  Rotate90,
+  Rotate180,
+  Rotate270,
+}
+
+impl Rotation {
+  /// The quarter turn a number of `degrees` amounts to, if it is one. Whole turns aren't
+  /// rotations, so 0 and 360 return `None`.
+  ///
+  /// Arguments:
+  ///
+  /// * `degrees` - f64
+  ///
+  /// Usage: ```Rotation::from_degrees(-90.0);```
+  pub fn from_degrees(degrees: f64) -> Option<Rotation> {
+    match degrees.rem_euclid(360.0) {
+      90.0 => Some(Rotation::Rotate90),
+      180.0 => Some(Rotation::Rotate180),
+      270.0 => Some(Rotation::Rotate270),
+      _ => None,
+    }
+  }
+}
+
+/// How pixels are sampled when an image is resampled at positions between them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Sampling {
+  /// The closest pixel. Fast and keeps hard edges, but jagged.
+  Nearest,
+  /// A weighted average of the 4 closest pixels.
+  Bilinear,
+  /// A cubic fit through the 16 closest pixels. Sharper than bilinear.
+  Bicubic,
+}
+
+impl Sampling {
+  /// The names `Sampling::from_str` accepts.
+  pub const NAMES: [&'static str; 3] = ["nearest", "bilinear", "bicubic"];
+}
+
+impl FromStr for Sampling {
+  type Err = String;
+
+  fn from_str(name: &str) -> std::result::Result<Self, Self::Err> {
+    match name {
+      "nearest" => Ok(Sampling::Nearest),
+      "bilinear" => Ok(Sampling::Bilinear),
+      "bicubic" => Ok(Sampling::Bicubic),
+      _ => Err(format!("must be one of {}", Sampling::NAMES.join(", "))),
+    }
+  }
+}
+
+impl fmt::Display for Sampling {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      Sampling::Nearest => write!(f, "nearest"),
+      Sampling::Bilinear => write!(f, "bilinear"),
+      Sampling::Bicubic => write!(f, "bicubic"),
+    }
+  }
+}
+
+/// The resampling filters `parse_filter` accepts, from fastest to sharpest.
+pub const FILTER_NAMES: [&str; 5] = ["nearest", "triangle", "catmull-rom", "gaussian", "lanczos3"];
+
+/// Parses a resampling filter by name.
+///
+/// Arguments:
+///
+/// * `name` - &str
+///
+/// Usage: ```transform::parse_filter("lanczos3")?;```
+pub fn parse_filter(name: &str) -> std::result::Result<FilterType, String> {
+  match name {
+    "nearest" => Ok(FilterType::Nearest),
+    "triangle" => Ok(FilterType::Triangle),
+    "catmull-rom" => Ok(FilterType::CatmullRom),
+    "gaussian" => Ok(FilterType::Gaussian),
+    "lanczos3" => Ok(FilterType::Lanczos3),
+    _ => Err(format!("must be one of {}", FILTER_NAMES.join(", "))),
+  }
+}
+
+/// How an image is fitted to a new size.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResizeMode {
+  /// Stretches the image to exactly the new size. A width or height of 0 follows the aspect ratio.
+  Exact,
+  /// Shrinks or grows the image to the largest size that fits inside the new size, keeping its
+  /// aspect ratio.
+  Fit,
+  /// Covers the new size while keeping the aspect ratio, cropping off whatever overhangs.
+  Fill,
+}
+
+impl ResizeMode {
+  /// The names `ResizeMode::from_str` accepts.
+  pub const NAMES: [&'static str; 3] = ["exact", "fit", "fill"];
+}
+
+impl FromStr for ResizeMode {
+  type Err = String;
+
+  fn from_str(name: &str) -> std::result::Result<Self, Self::Err> {
+    match name {
+      "exact" => Ok(ResizeMode::Exact),
+      "fit" => Ok(ResizeMode::Fit),
+      "fill" => Ok(ResizeMode::Fill),
+      _ => Err(format!("must be one of {}", ResizeMode::NAMES.join(", "))),
+    }
+  }
+}
+
+impl fmt::Display for ResizeMode {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      ResizeMode::Exact => write!(f, "exact"),
+      ResizeMode::Fit => write!(f, "fit"),
+      ResizeMode::Fill => write!(f, "fill"),
+    }
+  }
+}
+
+/// The direction an image is flipped in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Axis {
+  /// Left to right, as in a mirror.
+  Horizontal,
+  /// Top to bottom.
+  Vertical,
+}
+
+impl FromStr for Axis {
+  type Err = String;
+
+  fn from_str(name: &str) -> std::result::Result<Self, Self::Err> {
+    match name {
+      "horizontal" | "h" => Ok(Axis::Horizontal),
+      "vertical" | "v" => Ok(Axis::Vertical),
+      _ => Err("must be horizontal or vertical".to_string()),
+    }
+  }
+}
+
+/// The half or quadrant of an image that `mirror` reflects over the rest of it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MirrorRegion {
+  Left,
+  Right,
+  Top,
+  Bottom,
+  TopLeft,
+  TopRight,
+  BottomLeft,
+  BottomRight,
+}
+
+impl MirrorRegion {
+  /// The names `MirrorRegion::from_str` accepts.
+  pub const NAMES: [&'static str; 8] = [
+    "left",
+    "right",
+    "top",
+    "bottom",
+    "top-left",
+    "top-right",
+    "bottom-left",
+    "bottom-right",
+  ];
+
+  /// Which side of each axis is kept: `Some(true)` for the start (left or top), `Some(false)` for
+  /// the end and `None` when the axis isn't mirrored.
+  fn sides(self) -> (Option<bool>, Option<bool>) {
+    match self {
+      MirrorRegion::Left => (Some(true), None),
+      MirrorRegion::Right => (Some(false), None),
+      MirrorRegion::Top => (None, Some(true)),
+      MirrorRegion::Bottom => (None, Some(false)),
+      MirrorRegion::TopLeft => (Some(true), Some(true)),
+      MirrorRegion::TopRight => (Some(false), Some(true)),
+      MirrorRegion::BottomLeft => (Some(true), Some(false)),
+      MirrorRegion::BottomRight => (Some(false), Some(false)),
+    }
+  }
+}
+
+impl FromStr for MirrorRegion {
+  type Err = String;
+
+  fn from_str(name: &str) -> std::result::Result<Self, Self::Err> {
+    match name {
+      "left" => Ok(MirrorRegion::Left),
+      "right" => Ok(MirrorRegion::Right),
+      "top" => Ok(MirrorRegion::Top),
+      "bottom" => Ok(MirrorRegion::Bottom),
+      "top-left" => Ok(MirrorRegion::TopLeft),
+      "top-right" => Ok(MirrorRegion::TopRight),
+      "bottom-left" => Ok(MirrorRegion::BottomLeft),
+      "bottom-right" => Ok(MirrorRegion::BottomRight),
+      _ => Err(format!("must be one of {}", MirrorRegion::NAMES.join(", "))),
+    }
+  }
+}
+
+/// How to rotate an image by an arbitrary angle.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RotateOptions {
+  pub sampling: Sampling,
+  /// Grows the canvas to fit the whole rotated image, rather than cropping it to the original
+  /// size.
+  pub expand: bool,
+  /// The `[red, green, blue, alpha]` color of the areas the rotated image doesn't cover.
+  pub fill: [u8; 4],
+}
+
+impl Default for RotateOptions {
+  fn default() -> Self {
+    RotateOptions {
+      sampling: Sampling::Bilinear,
+      expand: true,
+      fill: [0, 0, 0, 255],
+    }
+  }
+}
+
+/// Blurs an image with a gaussian of standard deviation `sigma`.
+///
+/// Arguments:
+///
+/// * `img` - &DynamicImage
+/// * `sigma` - f32
+///
+/// Usage: ```transform::blur(&img, 2.5);```
+pub fn blur(img: &DynamicImage, sigma: f32) -> DynamicImage {
+  img.blur(sigma)
+}
+
+/// Brightens an image by an `amount`. Negative amounts darken it.
+///
+/// Arguments:
+///
+/// * `img` - &DynamicImage
+/// * `amount` - i32
+///
+/// Usage: ```transform::brighten(&img, 10);```
+pub fn brighten(img: &DynamicImage, amount: i32) -> DynamicImage {
+  img.brighten(amount)
+}
+
+/// Crops an image to `rect`, failing if the rectangle doesn't fit inside the image.
+///
+/// Arguments:
+///
+/// * `img` - &DynamicImage
+/// * `rect` - CropRect
+///
+/// Usage: ```transform::crop(&img, CropRect { x: 0, y: 0, width: 640, height: 480 })?;```
+pub fn crop(img: &DynamicImage, rect: CropRect) -> Result<DynamicImage> {
+  // .crop_imm() silently clamps the rectangle to the image, so reject anything that doesn't fit.
+  let (image_width, image_height) = img.dimensions();
+  if rect.width == 0
+    || rect.height == 0
+    || u64::from(rect.x) + u64::from(rect.width) > u64::from(image_width)
+    || u64::from(rect.y) + u64::from(rect.height) > u64::from(image_height)
+  {
+    return Err(ImageUtilsError::CropOutOfBounds {
+      x: rect.x,
+      y: rect.y,
+      width: rect.width,
+      height: rect.height,
+      image_width,
+      image_height,
+    });
+  }
+
+  Ok(img.crop_imm(rect.x, rect.y, rect.width, rect.height))
+}
+
+/// Flips an image along an `axis`.
+///
+/// Arguments:
+///
+/// * `img` - &DynamicImage
+/// * `axis` - Axis
+///
+/// Usage: ```transform::flip(&img, Axis::Horizontal);```
+pub fn flip(img: &DynamicImage, axis: Axis) -> DynamicImage {
+  match axis {
+    Axis::Horizontal => img.fliph(),
+    Axis::Vertical => img.flipv(),
+  }
+}
+
+/// Converts an image to grayscale.
+///
+/// Arguments:
+///
+/// * `img` - &DynamicImage
+///
+/// Usage: ```transform::grayscale(&img);```
+pub fn grayscale(img: &DynamicImage) -> DynamicImage {
+  img.grayscale()
+}
+
+/// Inverts the colors of an image.
+///
+/// Arguments:
+///
+/// * `img` - &DynamicImage
+///
+/// Usage: ```transform::invert(&img);```
+pub fn invert(img: &DynamicImage) -> DynamicImage {
+  let mut new_image = img.clone();
+  new_image.invert();
+  new_image
+}
+
+/// Reflects one half or quadrant of an image over the rest of it, like a kaleidoscope.
+///
+/// Arguments:
+///
+/// * `img` - &DynamicImage
+/// * `region` - MirrorRegion
+///
+/// Usage: ```transform::mirror(&img, MirrorRegion::TopLeft);```
+pub fn mirror(img: &DynamicImage, region: MirrorRegion) -> DynamicImage {
+  let (width, height) = img.dimensions();
+  let (keep_x, keep_y) = region.sides();
+  // Every pixel on the discarded side copies its reflection on the kept side.
+  let reflect = |position: u32, length: u32, keep: Option<bool>| {
+    let reflection = length - 1 - position;
+    match keep {
+      Some(true) => position.min(reflection),
+      Some(false) => position.max(reflection),
+      None => position,
+    }
+  };
+
+  map_buffer!(img, buffer => ImageBuffer::from_fn(width, height, |x, y| {
+    *buffer.get_pixel(reflect(x, width, keep_x), reflect(y, height, keep_y))
+  }))
+}
+
+/// Resizes an image to `width` x `height` pixels according to a `mode`.
+///
+/// Arguments:
+///
+/// * `img` - &DynamicImage
+/// * `width` - u32
+/// * `height` - u32
+/// * `mode` - ResizeMode
+/// * `filter` - FilterType
+///
+/// Usage: ```transform::resize(&img, 640, 0, ResizeMode::Exact, FilterType::Lanczos3);```
+pub fn resize(
+  img: &DynamicImage,
+  width: u32,
+  height: u32,
+  mode: ResizeMode,
+  filter: FilterType,
+) -> DynamicImage {
+  let (image_width, image_height) = img.dimensions();
+  // Scales `length` by `to / from`, never rounding down to nothing.
+  let follow = |length: u32, to: u32, from: u32| {
+    ((f64::from(length) * f64::from(to) / f64::from(from)).round() as u32).max(1)
+  };
+
+  match mode {
+    ResizeMode::Exact => {
+      let (width, height) = match (width, height) {
+        (0, height) => (follow(image_width, height, image_height), height),
+        (width, 0) => (width, follow(image_height, width, image_width)),
+        size => size,
+      };
+      img.resize_exact(width, height, filter)
+    }
+    ResizeMode::Fit => img.resize(width, height, filter),
+    ResizeMode::Fill => img.resize_to_fill(width, height, filter),
+  }
+}
+
+/// Scales both sides of an image by `percent`.
+///
+/// Arguments:
+///
+/// * `img` - &DynamicImage
+/// * `percent` - f64
+/// * `filter` - FilterType
+///
+/// Usage: ```transform::scale(&img, 50.0, FilterType::Triangle);```
+pub fn scale(img: &DynamicImage, percent: f64, filter: FilterType) -> DynamicImage {
+  let (width, height) = img.dimensions();
+  let scaled = |length: u32| ((f64::from(length) * percent / 100.0).round() as u32).max(1);
+
+  img.resize_exact(scaled(width), scaled(height), filter)
+}
+
+/// Shrinks an image to fit inside a `size` x `size` square, keeping its aspect ratio. Images that
+/// already fit are left alone.
+///
+/// Arguments:
+///
+/// * `img` - &DynamicImage
+/// * `size` - u32
+///
+/// Usage: ```transform::thumbnail(&img, 128);```
+pub fn thumbnail(img: &DynamicImage, size: u32) -> DynamicImage {
+  let (width, height) = img.dimensions();
+  if width <= size && height <= size {
+    return img.clone();
+  }
+
+  img.thumbnail(size, size)
+}
+
+/// Rotates an image clockwise by a `rotation`.
+///
+/// Arguments:
+///
+/// * `img` - &DynamicImage
+/// * `rotation` - Rotation
+///
+/// Usage: ```transform::rotate(&img, Rotation::Rotate180);```
+pub fn rotate(img: &DynamicImage, rotation: Rotation) -> DynamicImage {
+  match rotation {
+    Rotation::Rotate90 => img.rotate90(),
+    Rotation::Rotate180 => img.rotate180(),
+    Rotation::Rotate270 => img.rotate270(),
+  }
+}
+
+/// Reflects an image across its top-left to bottom-right diagonal, swapping rows and columns.
+///
+/// Arguments:
+///
+/// * `img` - &DynamicImage
+///
+/// Usage: ```transform::transpose(&img);```
+pub fn transpose(img: &DynamicImage) -> DynamicImage {
+  img.rotate90().fliph()
+}
+
+/// Reflects an image across its top-right to bottom-left diagonal.
+///
+/// Arguments:
+///
+/// * `img` - &DynamicImage
+///
+/// Usage: ```transform::transverse(&img);```
+pub fn transverse(img: &DynamicImage) -> DynamicImage {
+  img.rotate270().fliph()
+}
+
+/// Rotates an image clockwise by any number of `degrees`. Quarter turns that keep the whole image
+/// are lossless; anything else is resampled. The result keeps the image's color type unless the
+/// fill needs color or transparency it doesn't have, e.g. a red fill around a grayscale image.
+///
+/// Arguments:
+///
+/// * `img` - &DynamicImage
+/// * `degrees` - f64
+/// * `options` - RotateOptions
+///
+/// Usage: ```transform::rotate_by(&img, 30.0, RotateOptions::default());```
+pub fn rotate_by(img: &DynamicImage, degrees: f64, options: RotateOptions) -> DynamicImage {
+  let (width, height) = img.dimensions();
+  match Rotation::from_degrees(degrees) {
+    _ if degrees.rem_euclid(360.0) == 0.0 => return img.clone(),
+    Some(Rotation::Rotate180) => return img.rotate180(),
+    Some(rotation) if options.expand || width == height => return rotate(img, rotation),
+    _ => {}
+  }
+
+  let (sin, cos) = degrees.to_radians().sin_cos();
+  let (out_width, out_height) = if options.expand {
+    // Round away float noise so e.g. 45 degrees doesn't gain a pixel.
+    let fit = |a: u32, b: u32| {
+      (f64::from(a) * cos.abs() + f64::from(b) * sin.abs() - 1e-6)
+        .ceil()
+        .max(1.0) as u32
+    };
+    (fit(width, height), fit(height, width))
+  } else {
+    (width, height)
+  };
+
+  let transform = |x: u32, y: u32| {
+    // Undo the rotation around the middle of the output to find where a pixel came from.
+    let dx = f64::from(x) + 0.5 - f64::from(out_width) / 2.0;
+    let dy = f64::from(y) + 0.5 - f64::from(out_height) / 2.0;
+    (
+      dx * cos + dy * sin + f64::from(width) / 2.0 - 0.5,
+      -dx * sin + dy * cos + f64::from(height) / 2.0 - 0.5,
+    )
+  };
+  let rotated = if is_16_bit(img) {
+    DynamicImage::ImageRgba16(resample(
+      &img.to_rgba16(),
+      out_width,
+      out_height,
+      options,
+      transform,
+    ))
+  } else {
+    DynamicImage::ImageRgba8(resample(
+      &img.to_rgba8(),
+      out_width,
+      out_height,
+      options,
+      transform,
+    ))
+  };
+
+  let [red, green, blue, alpha] = options.fill;
+  let gray = img.color().channel_count() < 3;
+  let gray_fill = red == green && green == blue;
+  let has_alpha = img.color().has_alpha();
+  if (alpha == 255 || has_alpha) && (gray_fill || !gray) {
+    return with_color_type(rotated, img);
+  }
+
+  // The fill needs a channel the image doesn't have.
+  match (
+    gray && gray_fill,
+    alpha == 255 && !has_alpha,
+    is_16_bit(img),
+  ) {
+    (true, _, false) => DynamicImage::ImageLumaA8(rotated.to_luma_alpha8()),
+    (true, _, true) => DynamicImage::ImageLumaA16(rotated.to_luma_alpha16()),
+    (false, true, false) => DynamicImage::ImageRgb8(rotated.to_rgb8()),
+    (false, true, true) => DynamicImage::ImageRgb16(rotated.to_rgb16()),
+    (false, false, _) => rotated,
+  }
+}
+
+/// Whether an image stores 16 bits per channel.
+pub(crate) fn is_16_bit(img: &DynamicImage) -> bool {
+  matches!(
+    img,
+    DynamicImage::ImageLuma16(_)
+      | DynamicImage::ImageLumaA16(_)
+      | DynamicImage::ImageRgb16(_)
+      | DynamicImage::ImageRgba16(_)
+  )
+}
+
+/// Converts `adjusted` back to the color type of `original`.
+pub(crate) fn with_color_type(adjusted: DynamicImage, original: &DynamicImage) -> DynamicImage {
+  match original {
+    DynamicImage::ImageLuma8(_) => DynamicImage::ImageLuma8(adjusted.to_luma8()),
+    DynamicImage::ImageLumaA8(_) => DynamicImage::ImageLumaA8(adjusted.to_luma_alpha8()),
+    DynamicImage::ImageRgb8(_) => DynamicImage::ImageRgb8(adjusted.to_rgb8()),
+    DynamicImage::ImageRgba8(_) => DynamicImage::ImageRgba8(adjusted.to_rgba8()),
+    DynamicImage::ImageBgr8(_) => DynamicImage::ImageBgr8(adjusted.to_bgr8()),
+    DynamicImage::ImageBgra8(_) => DynamicImage::ImageBgra8(adjusted.to_bgra8()),
+    DynamicImage::ImageLuma16(_) => DynamicImage::ImageLuma16(adjusted.to_luma16()),
+    DynamicImage::ImageLumaA16(_) => DynamicImage::ImageLumaA16(adjusted.to_luma_alpha16()),
+    DynamicImage::ImageRgb16(_) => DynamicImage::ImageRgb16(adjusted.to_rgb16()),
+    DynamicImage::ImageRgba16(_) => DynamicImage::ImageRgba16(adjusted.to_rgba16()),
+  }
+}
+
+/// A channel type images are resampled in.
+pub(crate) trait Channel: Primitive + Into<f64> + 'static {
+  /// The brightest value a channel can hold.
+  const MAX_VALUE: f64;
+
+  /// Rounds and clamps a value into the channel's range.
+  fn from_f64(value: f64) -> Self;
+}
+
+impl Channel for u8 {
+  const MAX_VALUE: f64 = 255.0;
+
+  fn from_f64(value: f64) -> Self {
+    value.round().clamp(0.0, Self::MAX_VALUE) as u8
+  }
+}
+
+impl Channel for u16 {
+  const MAX_VALUE: f64 = 65535.0;
+
+  fn from_f64(value: f64) -> Self {
+    value.round().clamp(0.0, Self::MAX_VALUE) as u16
+  }
+}
+
+/// Builds an `out_width` x `out_height` image where every pixel is sampled from `src` at the
+/// position `source` maps it to, in `src` pixel coordinates. Positions outside `src` take the
+/// fill color. Inside, interpolation repeats the edge pixels of `src` rather than blending in the
+/// fill, so the image's own edges keep their colors.
+fn resample<S: Channel>(
+  src: &ImageBuffer<Rgba<S>, Vec<S>>,
+  out_width: u32,
+  out_height: u32,
+  options: RotateOptions,
+  source: impl Fn(u32, u32) -> (f64, f64),
+) -> ImageBuffer<Rgba<S>, Vec<S>> {
+  let mut fill = [0.0; 4];
+  for (channel, value) in fill.iter_mut().zip(options.fill.iter()) {
+    *channel = f64::from(*value) / 255.0 * S::MAX_VALUE;
+  }
+  let (width, height) = (i64::from(src.width()), i64::from(src.height()));
+  let texel = |x: i64, y: i64| -> [f64; 4] {
+    let pixel = src.get_pixel(x.clamp(0, width - 1) as u32, y.clamp(0, height - 1) as u32);
+    let mut rgba = [0.0; 4];
+    for (channel, value) in rgba.iter_mut().zip(pixel.0.iter()) {
+      *channel = (*value).into();
+    }
+    rgba
+  };
+
+  ImageBuffer::from_fn(out_width, out_height, |x, y| {
+    let (sx, sy) = source(x, y);
+    let (nearest_x, nearest_y) = (sx.round() as i64, sy.round() as i64);
+    let rgba = match options.sampling {
+      _ if !(0..width).contains(&nearest_x) || !(0..height).contains(&nearest_y) => fill,
+      Sampling::Nearest => texel(nearest_x, nearest_y),
+      Sampling::Bilinear => weighted(sx, sy, 0, 1, |t| 1.0 - t.abs(), texel),
+      Sampling::Bicubic => weighted(sx, sy, -1, 2, cubic, texel),
+    };
+
+    let mut pixel = [S::zero(); 4];
+    for (channel, value) in pixel.iter_mut().zip(rgba.iter()) {
+      *channel = S::from_f64(*value);
+    }
+    Rgba(pixel)
+  })
+}
+
+/// Sums the pixels from `first` to `last` columns and rows away from (`x`, `y`), weighted by
+/// `kernel` of their distance.
+fn weighted(
+  x: f64,
+  y: f64,
+  first: i64,
+  last: i64,
+  kernel: impl Fn(f64) -> f64,
+  texel: impl Fn(i64, i64) -> [f64; 4],
+) -> [f64; 4] {
+  let (x0, y0) = (x.floor() as i64, y.floor() as i64);
+  let mut sum = [0.0; 4];
+  for j in first..=last {
+    let wy = kernel(y - (y0 + j) as f64);
+    for i in first..=last {
+      let weight = kernel(x - (x0 + i) as f64) * wy;
+      for (total, value) in sum.iter_mut().zip(texel(x0 + i, y0 + j).iter()) {
+        *total += value * weight;
+      }
+    }
+  }
+
+  sum
+}
+
+/// The Catmull-Rom cubic convolution kernel.
+fn cubic(t: f64) -> f64 {
+  let a = -0.5;
+  let t = t.abs();
+  if t < 1.0 {
+    (a + 2.0) * t * t * t - (a + 3.0) * t * t + 1.0
+  } else if t < 2.0 {
+    a * t * t * t - 5.0 * a * t * t + 8.0 * a * t - 4.0 * a
+  } else {
+    0.0
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use image::GrayImage;
+
+  fn numbered(width: u32, height: u32) -> DynamicImage {
+    DynamicImage::ImageLuma8(GrayImage::from_fn(width, height, |x, y| {
+      image::Luma([(y * width + x) as u8])
+    }))
+  }
+
+  fn rect(x: u32, y: u32, width: u32, height: u32) -> CropRect {
+    CropRect {
+      x,
+      y,
+      width,
+      height,
+    }
+  }
+
+  #[test]
+  fn crops_keep_the_pixels_inside_the_rectangle() {
+    let cropped = crop(&numbered(4, 3), rect(1, 1, 3, 2)).unwrap();
+
+    assert_eq!(cropped.dimensions(), (3, 2));
+    assert_eq!(cropped.to_luma8().into_raw(), vec![5, 6, 7, 9, 10, 11]);
+  }
+
+  #[test]
+  fn crops_may_reach_but_not_pass_the_edges() {
+    let img = numbered(4, 3);
+
+    assert!(crop(&img, rect(0, 0, 4, 3)).is_ok());
+    assert!(crop(&img, rect(1, 0, 4, 3)).is_err());
+    assert!(crop(&img, rect(0, 1, 4, 3)).is_err());
+    assert!(crop(&img, rect(4, 0, 1, 1)).is_err());
+    assert!(crop(&img, rect(u32::MAX, 0, 2, 1)).is_err());
+  }
+
+  #[test]
+  fn empty_crops_are_rejected() {
+    assert!(matches!(
+      crop(&numbered(4, 3), rect(0, 0, 0, 3)),
+      Err(ImageUtilsError::CropOutOfBounds { width: 0, .. })
+    ));
+    assert!(crop(&numbered(4, 3), rect(0, 0, 4, 0)).is_err());
+  }
+
+  #[test]
+  fn quarter_turns_are_recognized_in_either_direction() {
+    assert_eq!(Rotation::from_degrees(90.0), Some(Rotation::Rotate90));
+    assert_eq!(Rotation::from_degrees(-270.0), Some(Rotation::Rotate90));
+    assert_eq!(Rotation::from_degrees(180.0), Some(Rotation::Rotate180));
+    assert_eq!(Rotation::from_degrees(-180.0), Some(Rotation::Rotate180));
+    assert_eq!(Rotation::from_degrees(-90.0), Some(Rotation::Rotate270));
+    assert_eq!(Rotation::from_degrees(630.0), Some(Rotation::Rotate270));
+  }
+
+  #[test]
+  fn whole_turns_and_other_angles_are_not_quarter_turns() {
+    for &degrees in [0.0, 360.0, -720.0, 45.0, 89.9, f64::NAN].iter() {
+      assert_eq!(Rotation::from_degrees(degrees), None, "{} degrees", degrees);
+    }
+  }
+
+  #[test]
+  fn rotating_keeps_the_color_type_when_the_fill_fits_it() {
+    let gray = DynamicImage::new_luma8(6, 4);
+    let rotate = |fill| {
+      rotate_by(
+        &gray,
+        30.0,
+        RotateOptions {
+          fill,
+          ..RotateOptions::default()
+        },
+      )
+      .color()
+    };
+
+    assert_eq!(rotate([0, 0, 0, 255]), image::ColorType::L8);
+    assert_eq!(rotate([0, 0, 0, 0]), image::ColorType::La8);
+    assert_eq!(rotate([255, 0, 0, 255]), image::ColorType::Rgb8);
+  }
+
+  #[test]
+  fn quarter_turns_move_pixels_without_resampling() {
+    let img = numbered(3, 2);
+    let turned = rotate_by(&img, -270.0, RotateOptions::default());
+
+    assert_eq!(turned.dimensions(), (2, 3));
+    assert_eq!(turned.to_luma8().into_raw(), vec![3, 0, 4, 1, 5, 2]);
+    assert_eq!(
+      rotate_by(&img, 360.0, RotateOptions::default()).to_luma8(),
+      img.to_luma8()
+    );
+  }
+
+  #[test]
+  fn resize_modes_fit_the_new_size_in_their_own_way() {
+    let img = numbered(8, 4);
+    let size =
+      |mode, width, height| resize(&img, width, height, mode, FilterType::Nearest).dimensions();
+
+    assert_eq!(size(ResizeMode::Exact, 3, 5), (3, 5));
+    assert_eq!(size(ResizeMode::Exact, 4, 0), (4, 2));
+    assert_eq!(size(ResizeMode::Exact, 0, 8), (16, 8));
+    assert_eq!(size(ResizeMode::Fit, 4, 4), (4, 2));
+    assert_eq!(size(ResizeMode::Fit, 16, 16), (16, 8));
+    assert_eq!(size(ResizeMode::Fill, 4, 4), (4, 4));
+    // Following the aspect ratio never shrinks a side to nothing.
+    assert_eq!(size(ResizeMode::Exact, 1, 0), (1, 1));
+  }
+
+  #[test]
+  fn scales_round_and_thumbnails_only_shrink() {
+    let img = numbered(8, 4);
+
+    assert_eq!(scale(&img, 50.0, FilterType::Nearest).dimensions(), (4, 2));
+    assert_eq!(scale(&img, 30.0, FilterType::Nearest).dimensions(), (2, 1));
+    assert_eq!(scale(&img, 1.0, FilterType::Nearest).dimensions(), (1, 1));
+    assert_eq!(thumbnail(&img, 4).dimensions(), (4, 2));
+    assert_eq!(thumbnail(&img, 100).to_luma8(), img.to_luma8());
+  }
+
+  #[test]
+  fn filters_and_modes_parse_from_their_names() {
+    for name in FILTER_NAMES.iter() {
+      assert!(parse_filter(name).is_ok(), "{}", name);
+    }
+    for name in ResizeMode::NAMES.iter() {
+      assert_eq!(name.parse::<ResizeMode>().unwrap().to_string(), *name);
+    }
+    assert!(parse_filter("bilinear").is_err());
+    assert!("stretch".parse::<ResizeMode>().is_err());
+  }
+
+  #[test]
+  fn flips_and_diagonal_reflections_move_pixels_into_place() {
+    let img = numbered(3, 2);
+    let raw = |img: DynamicImage| img.to_luma8().into_raw();
+
+    assert_eq!(raw(flip(&img, Axis::Horizontal)), vec![2, 1, 0, 5, 4, 3]);
+    assert_eq!(raw(flip(&img, Axis::Vertical)), vec![3, 4, 5, 0, 1, 2]);
+    assert_eq!(transpose(&img).dimensions(), (2, 3));
+    assert_eq!(raw(transpose(&img)), vec![0, 3, 1, 4, 2, 5]);
+    assert_eq!(raw(transverse(&img)), vec![5, 2, 4, 1, 3, 0]);
+    assert_eq!("h".parse::<Axis>(), Ok(Axis::Horizontal));
+    assert!("diagonal".parse::<Axis>().is_err());
+  }
+
+  #[test]
+  fn mirrors_reflect_the_kept_region_over_the_rest() {
+    let raw = |img: DynamicImage, region| mirror(&img, region).to_luma8().into_raw();
+
+    assert_eq!(
+      raw(numbered(4, 2), MirrorRegion::Left),
+      vec![0, 1, 1, 0, 4, 5, 5, 4]
+    );
+    assert_eq!(
+      raw(numbered(4, 2), MirrorRegion::Right),
+      vec![3, 2, 2, 3, 7, 6, 6, 7]
+    );
+    assert_eq!(
+      raw(numbered(4, 3), MirrorRegion::TopLeft),
+      vec![0, 1, 1, 0, 4, 5, 5, 4, 0, 1, 1, 0]
+    );
+    assert_eq!(
+      raw(numbered(2, 3), MirrorRegion::Bottom),
+      vec![4, 5, 2, 3, 4, 5]
+    );
+    // The middle column of an odd width is its own reflection.
+    assert_eq!(raw(numbered(3, 1), MirrorRegion::Left), vec![0, 1, 0]);
+    for name in MirrorRegion::NAMES.iter() {
+      assert!(name.parse::<MirrorRegion>().is_ok(), "{}", name);
+    }
+  }
+}
@@ -1,5 +1,6 @@
-use image_utils::*;
+use image_utils::{ImageUtilsError, Registry};
 use std::env;
+use std::process::exit;
 
 // FINAL PROJECT
 //
@@ -35,33 +36,52 @@ pub fn run() {
   //
   // Challenge: If you're feeling really ambitious, you could delete this code
   // and use the "clap" library instead: https://docs.rs/clap/2.32.0/clap/
-  let mut args: Vec<String> = env::args().skip(1).collect();
+  let args: Vec<String> = env::args().skip(1).collect();
+  // Every subcommand, pipeline stage and usage line comes from the registry. Register your own
+  // `ImageOp`s or `Command`s here to add subcommands.
+  let registry = Registry::with_builtins();
   if args.is_empty() {
-    print_usage_and_exit();
+    registry.print_usage();
+    exit(1);
   }
-  match args.remove(0).as_str() {
-    "blur" => blur(&mut args),
-    "brighten" => brighten(&mut args),
-    "crop" => crop(&mut args),
-    "fractal" => fractal(&mut args),
-    "generate" => generate(&mut args),
-    "grayscale" => grayscale(&mut args),
-    "help" => print_usage_and_exit(),
-    "invert" => invert(&mut args),
-    "rotate" => rotate(&mut args),
-    _ => print_usage_and_exit(),
+
+  if let Err(error) = registry.run(args) {
+    exit_with_error(&registry, error);
   }
 }
 
-// **SUPER CHALLENGE FOR LATER** - Let's face it, you don't have time for this during class.
-//
-// Make all of the subcommands stackable!
+/// Reports an `ImageUtilsError` and exits with a code describing what went wrong:
+///
+/// * `2` - the command was invoked incorrectly
+/// * `3` - a file couldn't be read or written
+/// * `4` - an image couldn't be decoded or encoded
+/// * `5` - some of the files in a batch failed
+fn exit_with_error(registry: &Registry, error: ImageUtilsError) -> ! {
+  println!("\n\x1b[31m[ERROR]: {}\x1b[0m\n", error);
+
+  let code = match error {
+    ImageUtilsError::ArgumentCount { .. }
+    | ImageUtilsError::BadArgument { .. }
+    | ImageUtilsError::CropOutOfBounds { .. }
+    | ImageUtilsError::UnknownSubcommand(_) => {
+      registry.print_commands();
+      2
+    }
+    ImageUtilsError::Io { .. } => 3,
+    ImageUtilsError::Decode { .. } | ImageUtilsError::Encode { .. } => 4,
+    ImageUtilsError::BatchFailed { .. } => 5,
+  };
+
+  exit(code);
+}
+
+// PIPELINES
 //
-// For example, if you run:
+// All of the image subcommands are stackable. For example, if you run:
 //
 //   cargo run input.png output.png blur 2.5 invert rotate 180 brighten 10
 //
-// ...then your program would:
+// ...then the program will:
 // - read input.png
 // - apply a blur of 2.5
 // - invert the colors
@@ -69,4 +89,5 @@ pub fn run() {
 // - brighten the image by 10
 // - and write the result to output.png
 //
-// Good luck!
+// The image is decoded once and encoded once; every stage runs in memory. The same chain can be
+// spelled `cargo run pipeline input.png output.png ...`, which is what `help pipeline` describes.